@@ -0,0 +1,78 @@
+//! Measures the throughput of pushing into a full history for different capacities.
+//!
+//! Run with `cargo bench --bench history_push`. The time per push of the ring buffer should not
+//! grow with the capacity of the history, unlike the previous storage which rotated all entries.
+
+use std::{hint::black_box, time::Instant};
+
+use tui_repl::history::History;
+
+const PUSHES: usize = 200_000;
+
+/// The storage of [History] before it was a ring buffer, the oldest entry is always in slot 0
+struct RotatingHistory<const N: usize> {
+    len: usize,
+    stored_commands: [Vec<char>; N],
+}
+
+impl<const N: usize> RotatingHistory<N> {
+    fn new() -> Self {
+        Self {
+            len: 0,
+            stored_commands: [(); N].map(|_| Vec::new()),
+        }
+    }
+
+    fn push(&mut self, command: Vec<char>) {
+        if command.is_empty() || command.iter().copied().all(char::is_whitespace) {
+            return;
+        }
+
+        if self.len == N {
+            self.stored_commands.rotate_left(1);
+            self.stored_commands[N - 1] = command;
+        } else {
+            self.stored_commands[self.len] = command;
+            self.len += 1;
+        }
+    }
+}
+
+/// Time pushing into a full history, `push` is called with the history and a command
+fn time_pushes<H, const N: usize>(mut history: H, push: impl Fn(&mut H, Vec<char>)) -> f64 {
+    let commands = (0..N)
+        .map(|i| format!("command {}", i).chars().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    for command in &commands {
+        push(&mut history, command.clone());
+    }
+
+    let start = Instant::now();
+    for i in 0..PUSHES {
+        push(&mut history, black_box(commands[i % N].clone()));
+    }
+    let elapsed = start.elapsed();
+    black_box(&history);
+
+    elapsed.as_nanos() as f64 / PUSHES as f64
+}
+
+fn bench_push<const N: usize>() {
+    let ring = time_pushes::<_, N>(History::<N>::new(), |history, command| {
+        history.push(command)
+    });
+    let rotating = time_pushes::<_, N>(RotatingHistory::<N>::new(), |history, command| {
+        history.push(command)
+    });
+
+    println!(
+        "capacity {:>5}: ring buffer {:>8.1} ns/push, rotating {:>8.1} ns/push",
+        N, ring, rotating
+    );
+}
+
+fn main() {
+    bench_push::<32>();
+    bench_push::<512>();
+    bench_push::<4096>();
+}
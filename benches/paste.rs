@@ -0,0 +1,38 @@
+//! Compares inserting a large paste at the start of the input char by char and in one splice.
+//!
+//! Run with `cargo bench --bench paste`. Inserting char by char moves the whole input for every
+//! char, so it grows quadratically with the size of the paste.
+
+use std::{hint::black_box, time::Instant};
+
+use tui_repl::Repl;
+
+const INPUT_LEN: usize = 20_000;
+const PASTE_LEN: usize = 20_000;
+
+fn main() {
+    let input = "i".repeat(INPUT_LEN);
+    let paste = "p".repeat(PASTE_LEN);
+
+    let mut chars = input.chars().collect::<Vec<_>>();
+    let start = Instant::now();
+    for (offset, c) in paste.chars().enumerate() {
+        chars.insert(offset, c);
+    }
+    println!(
+        "char by char: {:>8.2} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    black_box(&chars);
+
+    let mut repl = Repl::new();
+    repl.set_input(&input);
+    repl.editor_mut().set_cursor_char_index(0);
+    let start = Instant::now();
+    repl.insert_str_at_cursor(black_box(&paste));
+    println!(
+        "one splice:   {:>8.2} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    assert_eq!(repl.current_input(), chars.as_slice());
+}
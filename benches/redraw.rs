@@ -0,0 +1,58 @@
+//! Compares drawing a typing session with and without reusing the rendered text lines.
+//!
+//! Run with `cargo bench --bench redraw`. While typing only the input changes, so every frame
+//! after the first should reuse the text lines.
+
+use std::{hint::black_box, io, time::Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{backend::TestBackend, Terminal};
+use tui_repl::Repl;
+
+const OUTPUT_LINES: usize = 10_000;
+const KEYS: usize = 2_000;
+
+fn bench_typing(force_full_redraw: bool) -> io::Result<()> {
+    let mut repl = Repl::new();
+    for i in 0..OUTPUT_LINES {
+        repl.println(&format!("output line {} with some more text to render", i));
+    }
+    repl.set_prompt("> ");
+    repl.emit_prompt();
+    let mut term = Terminal::new(TestBackend::new(120, 40))?;
+
+    let mut full_redraws = 0;
+    let start = Instant::now();
+    for i in 0..KEYS {
+        let c = char::from(b'a' + (i % 26) as u8);
+        let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert!(repl.feed_key_event(&mut (), key)?.is_continue());
+        if force_full_redraw {
+            repl.text_mut();
+        }
+        if repl.needs_full_redraw() {
+            full_redraws += 1;
+        }
+        repl.draw(&mut term)?;
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:<14} {:>5} full / {:>5} partial redraws, {:>8.1} us/frame",
+        if force_full_redraw {
+            "full redraw:"
+        } else {
+            "reused lines:"
+        },
+        full_redraws,
+        KEYS - full_redraws,
+        elapsed.as_micros() as f64 / KEYS as f64
+    );
+    black_box(&repl);
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    bench_typing(true)?;
+    bench_typing(false)
+}
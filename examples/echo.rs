@@ -1,6 +1,6 @@
-use tui_repl::Repl;
+use tui_repl::{error::ReplError, Repl};
 
-fn main() -> std::io::Result<()> {
+fn main() -> Result<(), ReplError> {
     Repl::new_run_fullscreen(|cmd: String, out: &mut String| {
         out.push('\n');
         out.push_str(&cmd);
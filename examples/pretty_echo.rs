@@ -1,8 +1,7 @@
-use tui_repl::Repl;
+use tui_repl::{error::ReplError, Repl};
 
-fn main() -> std::io::Result<()> {
-    let mut repl = Repl::new();
-    repl.text_mut().push('>');
+fn main() -> Result<(), ReplError> {
+    let mut repl = Repl::with_text(">");
 
     repl.run_fullscreen(|cmd: String, out: &mut String| {
         out.push_str("\n>>");
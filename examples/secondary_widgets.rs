@@ -12,7 +12,7 @@ use tui::{
     Terminal,
 };
 
-use tui_repl::{Repl, util as tui_repl_util};
+use tui_repl::{util as tui_repl_util, Repl};
 
 fn main() -> std::io::Result<()> {
     crossterm::terminal::enable_raw_mode()?;
@@ -37,8 +37,7 @@ fn main() -> std::io::Result<()> {
 
 fn render<B: Backend>(term: &mut Terminal<B>) -> io::Result<()> {
     let mut texts = Vec::new();
-    let mut repl = Repl::new();
-    repl.text_mut().push('>');
+    let mut repl = Repl::with_text(">");
 
     loop {
         term.draw(|f| {
@@ -50,7 +49,7 @@ fn render<B: Backend>(term: &mut Terminal<B>) -> io::Result<()> {
 
             let size = f.size();
             let chunks = Layout::default()
-            .direction(Direction::Horizontal)
+                .direction(Direction::Horizontal)
                 .constraints([Constraint::Percentage(90), Constraint::Percentage(10)].as_ref())
                 .split(size);
             f.render_widget(
@@ -62,16 +61,16 @@ fn render<B: Backend>(term: &mut Terminal<B>) -> io::Result<()> {
             );
 
             let out_block = block.inner(chunks[1]);
-            let text = tui_repl_util::get_visible_text(texts.join("\n").as_ref(), out_block.height as usize);
-            f.render_widget(
-                Paragraph::new(text),
-                out_block,
+            let text = tui_repl_util::get_visible_text(
+                texts.join("\n").as_ref(),
+                out_block.height as usize,
             );
+            f.render_widget(Paragraph::new(text), out_block);
             let repl_block = block.inner(chunks[0]);
             f.render_widget(block, chunks[0]);
             f.render_widget(&mut repl, repl_block);
-            let (cursor_x, cursor_y) = repl.cursor_pos_in(repl_block);
-            f.set_cursor(cursor_x + repl_block.left(), cursor_y + repl_block.top());
+            let (cursor_x, cursor_y) = repl.cursor_pos_in_absolute(repl_block);
+            f.set_cursor(cursor_x, cursor_y);
         })?;
 
         let mut executor = |cmd: String, out: &mut String| {
@@ -82,9 +81,8 @@ fn render<B: Backend>(term: &mut Terminal<B>) -> io::Result<()> {
         };
 
         if let Event::Key(key) = event::read()? {
-            match repl.feed_key_event(&mut executor, key)? {
-                ControlFlow::Break(()) => return Ok(()),
-                _ => (),
+            if let ControlFlow::Break(()) = repl.feed_key_event(&mut executor, key)? {
+                return Ok(());
             }
         }
     }
@@ -92,7 +90,7 @@ fn render<B: Backend>(term: &mut Terminal<B>) -> io::Result<()> {
 
 fn run_command(cmd: String, texts: &mut Vec<String>) -> &'static str {
     let parts = cmd.split(' ').filter(|s| !s.is_empty()).collect::<Vec<_>>();
-    match parts.get(0).map(|s| *s) {
+    match parts.first().copied() {
         Some("add") => {
             texts.push(
                 parts
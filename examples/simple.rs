@@ -1,16 +1,15 @@
-use tui_repl::Repl;
+use tui_repl::{error::ReplError, Repl};
 
-fn main() -> std::io::Result<()> {
-    let mut repl = Repl::new();
-    repl.text_mut().push('>');
+fn main() -> Result<(), ReplError> {
+    let mut repl = Repl::with_text(">");
 
     repl.run_fullscreen(|cmd: String, out: &mut String| {
         let parts = cmd.split(' ').filter(|s| !s.is_empty()).collect::<Vec<_>>();
-        match parts.get(0).map(|s| *s) {
+        match parts.first().copied() {
             Some("echo") => {
                 out.push_str("\n>>");
                 if let Some(s) = parts.get(1) {
-                    out.push_str(&s);
+                    out.push_str(s);
                 }
                 out.push('\n');
             }
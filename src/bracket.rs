@@ -0,0 +1,78 @@
+//! Matching of the brackets (), [] and {} in the input
+
+/// The opening and closing bracket of every pair
+const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Get the index of the bracket matching the one at `idx`, respecting nesting
+///
+/// Returns `None` if there is no bracket at `idx` or it is unmatched. Brackets of other kinds
+/// are ignored, so `([)]` matches like `()` and `[]` on their own.
+pub fn matching(input: &[char], idx: usize) -> Option<usize> {
+    let bracket = *input.get(idx)?;
+    let mut depth = 0usize;
+    if let Some(&(open, close)) = PAIRS.iter().find(|(open, _)| *open == bracket) {
+        for (offset, &c) in input[idx + 1..].iter().enumerate() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                match depth.checked_sub(1) {
+                    Some(d) => depth = d,
+                    None => return Some(idx + 1 + offset),
+                }
+            }
+        }
+    } else if let Some(&(open, close)) = PAIRS.iter().find(|(_, close)| *close == bracket) {
+        for (i, &c) in input[..idx].iter().enumerate().rev() {
+            if c == close {
+                depth += 1;
+            } else if c == open {
+                match depth.checked_sub(1) {
+                    Some(d) => depth = d,
+                    None => return Some(i),
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Get the index of the bracket at the cursor, or else right before it, with its match
+pub fn at_cursor(input: &[char], cursor: usize) -> Option<(usize, usize)> {
+    [Some(cursor), cursor.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .find_map(|idx| matching(input, idx).map(|matched| (idx, matched)))
+}
+
+/// Whether the char is one of the brackets handled by [matching]
+pub fn is_bracket(c: char) -> bool {
+    PAIRS.iter().any(|&(open, close)| c == open || c == close)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_nested() {
+        let input = "f(a[0], {b: (c)})".chars().collect::<Vec<_>>();
+        assert_eq!(matching(&input, 1), Some(16));
+        assert_eq!(matching(&input, 16), Some(1));
+        assert_eq!(matching(&input, 3), Some(5));
+        assert_eq!(matching(&input, 8), Some(15));
+        assert_eq!(matching(&input, 12), Some(14));
+        assert_eq!(matching(&input, 14), Some(12));
+        assert_eq!(matching(&input, 0), None);
+    }
+
+    #[test]
+    fn unmatched() {
+        let input = "(a))".chars().collect::<Vec<_>>();
+        assert_eq!(matching(&input, 3), None);
+        assert_eq!(matching(&input[..2], 0), None);
+        assert_eq!(at_cursor(&input, 4), None);
+        assert_eq!(at_cursor(&input, 3), Some((2, 0)));
+        assert!(is_bracket('}') && !is_bracket('<'));
+    }
+}
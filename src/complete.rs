@@ -0,0 +1,196 @@
+//! Completion of the input and fuzzy matching of completion candidates
+
+use std::cmp::Reverse;
+
+/// A completer providing candidates for the input
+pub trait Completer {
+    /// Get the candidates completing the input, `cursor` is the char index of the cursor
+    ///
+    /// A chosen candidate replaces the word before the cursor, see [word_start].
+    fn complete(&self, input: &[char], cursor: usize) -> Vec<String>;
+}
+
+impl<F: Fn(&[char], usize) -> Vec<String>> Completer for F {
+    fn complete(&self, input: &[char], cursor: usize) -> Vec<String> {
+        self(input, cursor)
+    }
+}
+
+/// Get the index of the start of the word before the cursor, which completions replace
+pub fn word_start(input: &[char], cursor: usize) -> usize {
+    input[..cursor.min(input.len())]
+        .iter()
+        .rposition(|c| c.is_whitespace())
+        .map_or(0, |idx| idx + 1)
+}
+
+/// The candidates of an open completion menu and which of them is selected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionMenu {
+    candidates: Vec<String>,
+    selected: usize,
+    /// The char index of the start of the word replaced by the selected candidate
+    start: usize,
+}
+
+impl CompletionMenu {
+    /// Create a menu selecting the first candidate, `None` if there are no candidates
+    pub fn new(candidates: Vec<String>, start: usize) -> Option<Self> {
+        (!candidates.is_empty()).then_some(Self {
+            candidates,
+            selected: 0,
+            start,
+        })
+    }
+
+    /// Get all candidates in the order they are shown
+    pub fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+
+    /// Get the index of the selected candidate
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Get the selected candidate
+    pub fn selected_candidate(&self) -> &str {
+        &self.candidates[self.selected]
+    }
+
+    /// Get the char index of the start of the word replaced by the selected candidate
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Select the next candidate, wrapping around to the first
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.candidates.len();
+    }
+
+    /// Select the previous candidate, wrapping around to the last
+    pub fn select_prev(&mut self) {
+        self.selected = self
+            .selected
+            .checked_sub(1)
+            .unwrap_or(self.candidates.len() - 1);
+    }
+}
+
+/// Every matched char scores this
+const MATCH_SCORE: u32 = 1;
+/// Bonus for a char matched right after the previously matched one
+const CONSECUTIVE_BONUS: u32 = 2;
+/// Bonus for a char matched at the start of a word
+const WORD_START_BONUS: u32 = 3;
+
+/// Score how well the chars of `query` match `candidate` in order, ignoring case
+///
+/// Returns the score and the index of the first matched char, `None` if not all chars of the
+/// query are found in the candidate.
+pub fn fuzzy_score(candidate: &str, query: &str) -> Option<(u32, usize)> {
+    let mut query = query.chars().peekable();
+    let mut score = 0;
+    let mut first_match = None;
+    let mut last_match = None;
+    let mut prev = None;
+    for (idx, c) in candidate.chars().enumerate() {
+        let Some(&q) = query.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(q.to_lowercase()) {
+            query.next();
+            score += MATCH_SCORE;
+            if last_match.is_some_and(|last| last + 1 == idx) {
+                score += CONSECUTIVE_BONUS;
+            }
+            if !prev.is_some_and(char::is_alphanumeric) {
+                score += WORD_START_BONUS;
+            }
+            first_match.get_or_insert(idx);
+            last_match = Some(idx);
+        }
+        prev = Some(c);
+    }
+
+    match query.peek() {
+        Some(_) => None,
+        None => Some((score, first_match.unwrap_or(0))),
+    }
+}
+
+/// Keep the candidates fuzzy matching the input, best matches first
+///
+/// Chars of the input have to appear in the candidate in order but not next to each other,
+/// so "grcl" matches "git reset --hard clean". Matches at word starts and runs of consecutive
+/// chars score higher. On equal score the candidate whose match starts first wins, then the
+/// one coming first.
+pub fn fuzzy_filter<'a>(
+    candidates: impl IntoIterator<Item = &'a str>,
+    input: &str,
+) -> Vec<(u32, &'a str)> {
+    let mut matches = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            fuzzy_score(candidate, input).map(|(score, start)| (score, start, candidate))
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|&(score, start, _)| (Reverse(score), start));
+
+    matches
+        .into_iter()
+        .map(|(score, _, candidate)| (score, candidate))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ranked<'a>(candidates: &[&'a str], input: &str) -> Vec<&'a str> {
+        fuzzy_filter(candidates.iter().copied(), input)
+            .into_iter()
+            .map(|(_, candidate)| candidate)
+            .collect()
+    }
+
+    #[test]
+    fn menu_selection_wraps() {
+        assert_eq!(CompletionMenu::new(Vec::new(), 0), None);
+        let mut menu = CompletionMenu::new(vec!["a".into(), "b".into()], 0).unwrap();
+        menu.select_prev();
+        assert_eq!(menu.selected_candidate(), "b");
+        menu.select_next();
+        assert_eq!(menu.selected_candidate(), "a");
+    }
+
+    #[test]
+    fn ranks_fuzzy_matches() {
+        let candidates = [
+            "cargo clean",
+            "grep color",
+            "git rebase",
+            "git reset --hard clean",
+        ];
+        assert_eq!(
+            ranked(&candidates, "grcl"),
+            ["git reset --hard clean", "grep color"]
+        );
+        assert_eq!(
+            ranked(&candidates, "GRCL"),
+            ["git reset --hard clean", "grep color"]
+        );
+        assert_eq!(ranked(&candidates, "xyz"), [] as [&str; 0]);
+        assert_eq!(ranked(&candidates, ""), candidates);
+    }
+
+    #[test]
+    fn ties_broken_by_match_position() {
+        assert_eq!(
+            fuzzy_score("x ab", "ab"),
+            fuzzy_score("ab x", "ab").map(|(s, _)| (s, 2))
+        );
+        assert_eq!(ranked(&["x ab", "ab x"], "ab"), ["ab x", "x ab"]);
+        assert_eq!(ranked(&["ab y", "ab x"], "ab"), ["ab y", "ab x"]);
+    }
+}
@@ -0,0 +1,811 @@
+//! Editing of a single input line, independent of the terminal and of tui
+
+use std::{collections::VecDeque, mem, ops::Range};
+
+use crate::{
+    bracket,
+    history::History,
+    word::{self, WordClass},
+    UNDO_LIMIT,
+};
+
+/// An edit of the input or a movement of the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Insert a char at the cursor
+    Insert(char),
+    /// Move the cursor one char to the left
+    MoveLeft,
+    /// Move the cursor one char to the right
+    MoveRight,
+    /// Move the cursor to the start of the previous word
+    MoveWordLeft,
+    /// Move the cursor to the end of the next word
+    MoveWordRight,
+    /// Move the cursor to the start of the input
+    MoveHome,
+    /// Move the cursor to the end of the input
+    MoveEnd,
+    /// Move the cursor to the same column in the previous line of the input
+    MoveLineUp,
+    /// Move the cursor to the same column in the next line of the input
+    MoveLineDown,
+    /// Move the cursor onto the bracket matching the one at or before the cursor
+    MoveMatchingBracket,
+    /// Delete the char before the cursor
+    DeleteBackward,
+    /// Delete the char after the cursor
+    DeleteForward,
+    /// Delete up to the start of the previous word
+    DeleteWordBackward,
+    /// Delete up to the end of the next word
+    DeleteWordForward,
+    /// Replace the input with the previous history entry
+    HistoryPrev,
+    /// Replace the input with the next history entry, or an empty line after the newest one
+    HistoryNext,
+    /// Revert the last edit of the input
+    Undo,
+    /// Discard the whole input
+    Clear,
+}
+
+impl Action {
+    /// Whether the action changes the input, as opposed to only moving the cursor
+    pub fn is_edit(self) -> bool {
+        !matches!(
+            self,
+            Action::MoveLeft
+                | Action::MoveRight
+                | Action::MoveWordLeft
+                | Action::MoveWordRight
+                | Action::MoveHome
+                | Action::MoveEnd
+                | Action::MoveLineUp
+                | Action::MoveLineDown
+                | Action::MoveMatchingBracket
+        )
+    }
+}
+
+/// How a line break inserted into the input is indented
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AutoIndent {
+    /// The new line is not indented
+    #[default]
+    Off,
+    /// The new line starts with the leading whitespace of the line the line break is inserted in
+    Keep,
+    /// Like [AutoIndent::Keep], indented by another level after an opening bracket
+    Brackets,
+}
+
+/// The indentation added per level by [AutoIndent::Brackets]
+const INDENT: &str = "    ";
+
+/// Where [Action::MoveHome] and [Action::MoveEnd] move the cursor
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HomeEnd {
+    /// To the start or end of the whole input
+    #[default]
+    Input,
+    /// To the start or end of the line the cursor is in, for input with line breaks
+    ///
+    /// The input is not soft wrapped, so its lines are only separated by line breaks.
+    Line,
+}
+
+/// Where the cursor is placed in an entry recalled from the history
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryCursor {
+    /// At the end of the entry
+    #[default]
+    End,
+    /// At the same column as before, clamped to the length of the entry
+    ///
+    /// The column is remembered while moving through the history, so moving past a short
+    /// entry doesn't lose it.
+    KeepColumn,
+}
+
+/// The input line with its cursor, history and undo stack
+///
+/// This holds all the editing logic of the [Repl](crate::Repl), it can be used to build a
+/// repl on top of another ui toolkit.
+#[derive(Debug, Default)]
+pub struct LineEditor<const HISTORY_SIZE: usize> {
+    input: Vec<char>,
+    /// The number of chars after the cursor
+    cursor_pos: u16,
+    history: History<HISTORY_SIZE>,
+    word_class: WordClass,
+    undo_stack: VecDeque<(Vec<char>, u16)>,
+    history_cursor: HistoryCursor,
+    /// The column kept while moving through the history with [HistoryCursor::KeepColumn]
+    history_column: Option<usize>,
+    /// The column kept while moving between lines of the input
+    line_column: Option<usize>,
+    history_wrap: bool,
+    auto_indent: AutoIndent,
+    home_end: HomeEnd,
+    /// Whether Left and Right stop at line breaks instead of moving over them
+    stop_at_line_edges: bool,
+}
+
+impl<const HISTORY_SIZE: usize> LineEditor<HISTORY_SIZE> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_with_history(history: History<HISTORY_SIZE>) -> Self {
+        Self {
+            history,
+            ..Default::default()
+        }
+    }
+
+    /// Apply an action to the input
+    ///
+    /// Returns `false` if the action ran into a boundary, which is the end of the history or
+    /// an empty undo stack. The [Repl](crate::Repl) rings the bell in that case.
+    pub fn apply(&mut self, action: Action) -> bool {
+        let history_column = self.history_column.take();
+        let line_column = self.line_column.take();
+        // The input may have been shortened through input_mut, indices are computed from the
+        // cursor below
+        self.clamp_cursor();
+        match action {
+            Action::Insert('\n') if self.auto_indent != AutoIndent::Off => {
+                let indented = self.indent_line_break();
+                self.insert_str(&indented);
+            }
+            Action::Insert(c) => {
+                self.save_undo();
+                let idx = self.cursor_char_index();
+                self.input.insert(idx, c);
+            }
+            Action::MoveLeft => {
+                let idx = self.cursor_char_index();
+                let at_edge =
+                    idx.checked_sub(1).and_then(|prev| self.input.get(prev)) == Some(&'\n');
+                if !(self.stop_at_line_edges && at_edge) {
+                    self.set_cursor_pos(self.cursor_pos.saturating_add(1));
+                }
+            }
+            Action::MoveRight => {
+                let at_edge = self.input.get(self.cursor_char_index()) == Some(&'\n');
+                if !(self.stop_at_line_edges && at_edge) {
+                    self.set_cursor_pos(self.cursor_pos.saturating_sub(1));
+                }
+            }
+            Action::MoveWordLeft => {
+                let idx =
+                    word::prev_boundary(&self.input, self.cursor_char_index(), self.word_class);
+                self.set_cursor_char_index(idx);
+            }
+            Action::MoveWordRight => {
+                let idx =
+                    word::next_boundary(&self.input, self.cursor_char_index(), self.word_class);
+                self.set_cursor_char_index(idx);
+            }
+            Action::MoveHome if self.home_end == HomeEnd::Line => {
+                let idx = self.cursor_char_index();
+                let line_start = self.input[..idx]
+                    .iter()
+                    .rposition(|&c| c == '\n')
+                    .map_or(0, |idx| idx + 1);
+                self.set_cursor_char_index(line_start);
+            }
+            Action::MoveEnd if self.home_end == HomeEnd::Line => {
+                let idx = self.cursor_char_index();
+                let line_end = self.input[idx..]
+                    .iter()
+                    .position(|&c| c == '\n')
+                    .map_or(self.input.len(), |offset| idx + offset);
+                self.set_cursor_char_index(line_end);
+            }
+            Action::MoveLineUp | Action::MoveLineDown => {
+                let idx = self.cursor_char_index();
+                let line_start = self.input[..idx]
+                    .iter()
+                    .rposition(|&c| c == '\n')
+                    .map_or(0, |idx| idx + 1);
+                let column = line_column.unwrap_or(idx - line_start);
+                let target_start = if action == Action::MoveLineUp {
+                    let Some(prev_end) = line_start.checked_sub(1) else {
+                        return false;
+                    };
+                    self.input[..prev_end]
+                        .iter()
+                        .rposition(|&c| c == '\n')
+                        .map_or(0, |idx| idx + 1)
+                } else {
+                    match self.input[idx..].iter().position(|&c| c == '\n') {
+                        Some(offset) => idx + offset + 1,
+                        None => return false,
+                    }
+                };
+                let target_len = self.input[target_start..]
+                    .iter()
+                    .position(|&c| c == '\n')
+                    .unwrap_or(self.input.len() - target_start);
+                self.set_cursor_char_index(target_start + column.min(target_len));
+                self.line_column = Some(column);
+            }
+            Action::MoveMatchingBracket => {
+                match bracket::at_cursor(&self.input, self.cursor_char_index()) {
+                    Some((_, matched)) => self.set_cursor_char_index(matched),
+                    None => return false,
+                }
+            }
+            Action::MoveHome => self.cursor_pos = self.max_cursor_pos(),
+            Action::MoveEnd => self.set_cursor_pos(0),
+            Action::DeleteBackward => {
+                let rm_idx = self.cursor_char_index();
+                if rm_idx != 0 {
+                    self.save_undo();
+                    self.input.remove(rm_idx - 1);
+                }
+            }
+            Action::DeleteForward => {
+                if self.cursor_pos != 0 {
+                    self.save_undo();
+                    self.input.remove(self.cursor_char_index());
+                    self.cursor_pos -= 1;
+                }
+            }
+            Action::DeleteWordBackward => {
+                let end = self.cursor_char_index();
+                let start = word::prev_boundary(&self.input, end, self.word_class);
+                self.save_undo();
+                self.input.drain(start..end);
+            }
+            Action::DeleteWordForward => {
+                let start = self.cursor_char_index();
+                let end = word::next_boundary(&self.input, start, self.word_class);
+                self.save_undo();
+                self.input.drain(start..end);
+                self.cursor_pos -= (end - start) as u16;
+            }
+            Action::HistoryPrev | Action::HistoryNext => {
+                let prev = action == Action::HistoryPrev;
+                let mut at_end = if prev {
+                    self.history.is_empty() || self.history.current_index() == Some(0)
+                } else {
+                    self.history.current_index().is_none()
+                };
+                let wrap = at_end && self.history_wrap && !self.history.is_empty();
+                if wrap {
+                    at_end = false;
+                }
+
+                let column = history_column.unwrap_or_else(|| self.cursor_char_index());
+
+                self.save_undo();
+                self.input = (if wrap && prev {
+                    self.history.reset_current();
+                    self.history.prev()
+                } else if wrap {
+                    self.history.seek(0);
+                    self.history.current()
+                } else if prev {
+                    self.history.prev()
+                } else {
+                    self.history.next()
+                })
+                .unwrap_or(&[])
+                .to_vec();
+                self.cursor_pos = 0;
+                if self.history_cursor == HistoryCursor::KeepColumn {
+                    self.set_cursor_char_index(column);
+                    self.history_column = Some(column);
+                }
+                return !at_end;
+            }
+            Action::Clear => {
+                self.save_undo();
+                self.input.clear();
+                self.cursor_pos = 0;
+            }
+            Action::Undo => match self.undo_stack.pop_back() {
+                Some((input, cursor_pos)) => {
+                    self.input = input;
+                    self.cursor_pos = cursor_pos;
+                }
+                None => return false,
+            },
+        }
+
+        true
+    }
+
+    pub fn input(&self) -> &[char] {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut Vec<char> {
+        &mut self.input
+    }
+
+    /// Replace the input with the given text and place the cursor at its end
+    pub fn set_input(&mut self, text: &str) {
+        self.input = text.chars().collect();
+        self.cursor_pos = 0;
+    }
+
+    /// Insert text at the cursor as a single edit
+    ///
+    /// The chars after the cursor are moved once, so this is fast for large pastes.
+    pub fn insert_str(&mut self, s: &str) {
+        self.save_undo();
+        let idx = self.cursor_char_index();
+        self.input.splice(idx..idx, s.chars());
+    }
+
+    /// Replace the chars in `range` with the text as a single edit, placing the cursor after it
+    pub fn replace_range(&mut self, range: Range<usize>, s: &str) {
+        self.save_undo();
+        let end = range.start + s.chars().count();
+        self.input.splice(range, s.chars());
+        self.set_cursor_char_index(end);
+    }
+
+    /// Take the input, leaving an empty line behind and forgetting all edits
+    pub fn take_input(&mut self) -> Vec<char> {
+        self.cursor_pos = 0;
+        self.undo_stack.clear();
+        mem::take(&mut self.input)
+    }
+
+    /// Whether the input is not empty
+    pub fn has_input(&self) -> bool {
+        !self.input.is_empty()
+    }
+
+    /// Whether the input is not empty and not an unmodified history entry
+    pub fn is_dirty(&self) -> bool {
+        self.has_input() && self.history.current() != Some(self.input.as_slice())
+    }
+
+    /// The number of chars after the cursor
+    pub fn cursor_pos(&self) -> u16 {
+        self.cursor_pos
+    }
+
+    /// Set the number of chars after the cursor, clamped to the length of the input
+    ///
+    /// The cursor can't be placed more than [u16::MAX] chars before the end of the input, in longer
+    /// inputs it stops there.
+    pub fn set_cursor_pos(&mut self, pos: u16) {
+        self.cursor_pos = pos.min(self.max_cursor_pos());
+    }
+
+    /// Move the cursor back into the input if it was shortened through [Self::input_mut]
+    pub fn clamp_cursor(&mut self) {
+        self.set_cursor_pos(self.cursor_pos);
+    }
+
+    fn max_cursor_pos(&self) -> u16 {
+        u16::try_from(self.input.len()).unwrap_or(u16::MAX)
+    }
+
+    /// The index in the input the cursor is at
+    pub fn cursor_char_index(&self) -> usize {
+        self.input.len().saturating_sub(self.cursor_pos as usize)
+    }
+
+    /// Place the cursor at an index in the input
+    pub fn set_cursor_char_index(&mut self, idx: usize) {
+        let pos = self.input.len().saturating_sub(idx);
+        self.set_cursor_pos(u16::try_from(pos).unwrap_or(u16::MAX));
+    }
+
+    pub fn history(&self) -> &History<HISTORY_SIZE> {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut History<HISTORY_SIZE> {
+        &mut self.history
+    }
+
+    /// Consume the editor, returning its history
+    pub fn into_history(self) -> History<HISTORY_SIZE> {
+        self.history
+    }
+
+    /// Get what separates words for word wise movement and deletion
+    pub fn word_class(&self) -> WordClass {
+        self.word_class
+    }
+
+    /// Set what separates words for word wise movement and deletion
+    pub fn set_word_class(&mut self, word_class: WordClass) {
+        self.word_class = word_class;
+    }
+
+    /// Get where the cursor is placed in an entry recalled from the history
+    pub fn history_cursor(&self) -> HistoryCursor {
+        self.history_cursor
+    }
+
+    /// Set where the cursor is placed in an entry recalled from the history
+    pub fn set_history_cursor(&mut self, history_cursor: HistoryCursor) {
+        self.history_cursor = history_cursor;
+    }
+
+    /// Whether moving past the oldest or newest history entry continues at the other end
+    pub fn history_wrap(&self) -> bool {
+        self.history_wrap
+    }
+
+    /// Set whether moving past the oldest or newest history entry continues at the other end
+    ///
+    /// Moving up from the oldest entry recalls the newest one and moving down from the empty
+    /// line after the newest entry recalls the oldest one.
+    pub fn set_history_wrap(&mut self, history_wrap: bool) {
+        self.history_wrap = history_wrap;
+    }
+
+    /// Get where Home and End move the cursor
+    pub fn home_end(&self) -> HomeEnd {
+        self.home_end
+    }
+
+    /// Set where Home and End move the cursor
+    pub fn set_home_end(&mut self, home_end: HomeEnd) {
+        self.home_end = home_end;
+    }
+
+    /// Whether Left and Right move over line breaks to the previous and next line
+    pub fn line_edge_wrap(&self) -> bool {
+        !self.stop_at_line_edges
+    }
+
+    /// Set whether Left and Right move over line breaks to the previous and next line
+    pub fn set_line_edge_wrap(&mut self, wrap: bool) {
+        self.stop_at_line_edges = !wrap;
+    }
+
+    /// Get how a line break inserted into the input is indented
+    pub fn auto_indent(&self) -> AutoIndent {
+        self.auto_indent
+    }
+
+    /// Set how a line break inserted into the input is indented
+    pub fn set_auto_indent(&mut self, auto_indent: AutoIndent) {
+        self.auto_indent = auto_indent;
+    }
+
+    /// A line break followed by the indentation of the new line
+    fn indent_line_break(&self) -> String {
+        let before_cursor = &self.input[..self.cursor_char_index()];
+        let line_start = before_cursor
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |idx| idx + 1);
+        let line = &before_cursor[line_start..];
+
+        let mut indented = String::from("\n");
+        indented.extend(line.iter().take_while(|&&c| c == ' ' || c == '\t'));
+        let opens_block = line
+            .iter()
+            .rev()
+            .find(|c| !c.is_whitespace())
+            .is_some_and(|c| matches!(c, '(' | '[' | '{'));
+        if self.auto_indent == AutoIndent::Brackets && opens_block {
+            indented.push_str(INDENT);
+        }
+
+        indented
+    }
+
+    /// Remember the input and cursor before an edit so that it can be undone
+    fn save_undo(&mut self) {
+        if self.undo_stack.len() == UNDO_LIMIT {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack
+            .push_back((self.input.clone(), self.cursor_pos));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn type_str<const N: usize>(editor: &mut LineEditor<N>, s: &str) {
+        for c in s.chars() {
+            assert!(editor.apply(Action::Insert(c)));
+        }
+    }
+
+    fn input_string<const N: usize>(editor: &LineEditor<N>) -> String {
+        editor.input().iter().collect()
+    }
+
+    #[test]
+    fn insert_and_move() {
+        let mut editor = LineEditor::<4>::new();
+        type_str(&mut editor, "ac");
+        editor.apply(Action::MoveLeft);
+        editor.apply(Action::Insert('b'));
+        assert_eq!(input_string(&editor), "abc");
+        assert_eq!(editor.cursor_char_index(), 2);
+
+        editor.apply(Action::MoveHome);
+        editor.apply(Action::Insert('>'));
+        editor.apply(Action::MoveEnd);
+        editor.apply(Action::Insert('<'));
+        assert_eq!(input_string(&editor), ">abc<");
+    }
+
+    #[test]
+    fn delete_and_undo() {
+        let mut editor = LineEditor::<4>::new();
+        type_str(&mut editor, "foo bar");
+        editor.apply(Action::DeleteWordBackward);
+        assert_eq!(input_string(&editor), "foo ");
+        editor.apply(Action::MoveHome);
+        editor.apply(Action::DeleteForward);
+        assert_eq!(input_string(&editor), "oo ");
+
+        assert!(editor.apply(Action::Undo));
+        assert!(editor.apply(Action::Undo));
+        assert_eq!(input_string(&editor), "foo bar");
+        assert_eq!(editor.cursor_pos(), 0);
+    }
+
+    #[test]
+    fn history_reports_boundaries() {
+        let mut editor =
+            LineEditor::new_with_history(History::<4>::with_initial([vec!['a'], vec!['b']]));
+
+        assert!(editor.apply(Action::HistoryPrev));
+        assert_eq!(input_string(&editor), "b");
+        assert!(editor.apply(Action::HistoryPrev));
+        assert!(!editor.apply(Action::HistoryPrev));
+        assert_eq!(input_string(&editor), "a");
+        assert!(!editor.is_dirty());
+
+        assert!(editor.apply(Action::HistoryNext));
+        assert!(editor.apply(Action::HistoryNext));
+        assert!(editor.input().is_empty());
+        assert!(!editor.apply(Action::HistoryNext));
+    }
+
+    #[test]
+    fn take_input_forgets_edits() {
+        let mut editor = LineEditor::<4>::new();
+        type_str(&mut editor, "ls");
+        editor.apply(Action::MoveLeft);
+
+        assert_eq!(editor.take_input(), ['l', 's']);
+        assert_eq!(editor.cursor_pos(), 0);
+        assert!(!editor.apply(Action::Undo));
+    }
+
+    #[test]
+    fn history_keeps_column() {
+        let mut editor = LineEditor::new_with_history(History::<4>::with_initial(
+            ["abcdef", "ab", "abcdefgh"].map(|s| s.chars().collect()),
+        ));
+        editor.set_history_cursor(HistoryCursor::KeepColumn);
+        type_str(&mut editor, "xyzw");
+        editor.apply(Action::MoveLeft);
+
+        editor.apply(Action::HistoryPrev);
+        assert_eq!(editor.cursor_char_index(), 3);
+        editor.apply(Action::HistoryPrev);
+        assert_eq!(input_string(&editor), "ab");
+        assert_eq!(editor.cursor_char_index(), 2);
+        editor.apply(Action::HistoryPrev);
+        assert_eq!(editor.cursor_char_index(), 3);
+
+        editor.apply(Action::MoveLeft);
+        editor.apply(Action::HistoryNext);
+        assert_eq!(editor.cursor_char_index(), 2);
+
+        editor.set_history_cursor(HistoryCursor::End);
+        editor.apply(Action::HistoryNext);
+        assert_eq!(editor.cursor_pos(), 0);
+    }
+
+    #[test]
+    fn history_wraps_around() {
+        let mut editor = LineEditor::new_with_history(History::<4>::with_initial(
+            ["a", "b", "c"].map(|s| s.chars().collect()),
+        ));
+        editor.apply(Action::HistoryPrev);
+        editor.apply(Action::HistoryPrev);
+        editor.apply(Action::HistoryPrev);
+        assert!(!editor.apply(Action::HistoryPrev));
+        assert_eq!(input_string(&editor), "a");
+
+        editor.set_history_wrap(true);
+        assert!(editor.apply(Action::HistoryPrev));
+        assert_eq!(input_string(&editor), "c");
+        assert_eq!(editor.history().current_index(), Some(2));
+
+        assert!(editor.apply(Action::HistoryNext));
+        assert_eq!(input_string(&editor), "");
+        assert!(editor.apply(Action::HistoryNext));
+        assert_eq!(input_string(&editor), "a");
+        assert_eq!(editor.history().current_index(), Some(0));
+    }
+
+    #[test]
+    fn history_wrap_without_entries() {
+        let mut editor = LineEditor::<4>::new();
+        editor.set_history_wrap(true);
+        assert!(!editor.apply(Action::HistoryPrev));
+        assert!(!editor.apply(Action::HistoryNext));
+        assert_eq!(input_string(&editor), "");
+    }
+
+    #[test]
+    fn clamp_cursor_after_shortening() {
+        let mut editor = LineEditor::<4>::new();
+        type_str(&mut editor, "abcdef");
+        editor.set_cursor_pos(5);
+        editor.input_mut().truncate(2);
+        editor.clamp_cursor();
+        assert_eq!(editor.cursor_pos(), 2);
+        assert_eq!(editor.cursor_char_index(), 0);
+    }
+
+    #[test]
+    fn cursor_in_input_longer_than_u16() {
+        let mut editor = LineEditor::<4>::new();
+        let len = u16::MAX as usize + 10;
+        editor.set_input(&"a".repeat(len));
+        editor.apply(Action::MoveHome);
+        assert_eq!(editor.cursor_pos(), u16::MAX);
+        assert_eq!(editor.cursor_char_index(), 10);
+
+        editor.set_cursor_char_index(0);
+        assert_eq!(editor.cursor_pos(), u16::MAX);
+        editor.apply(Action::Insert('b'));
+        assert_eq!(editor.input()[10], 'b');
+        assert_eq!(editor.input().len(), len + 1);
+    }
+
+    #[test]
+    fn auto_indent_line_breaks() {
+        let mut editor = LineEditor::<4>::new();
+        editor.set_auto_indent(AutoIndent::Keep);
+        type_str(&mut editor, "fn f() {\n\tlet a = [\n");
+        assert_eq!(input_string(&editor), "fn f() {\n\tlet a = [\n\t");
+
+        editor.set_auto_indent(AutoIndent::Brackets);
+        editor.apply(Action::Undo);
+        type_str(&mut editor, "\n1,\n");
+        assert_eq!(
+            input_string(&editor),
+            "fn f() {\n\tlet a = [\n\t    1,\n\t    "
+        );
+
+        editor.set_input("  a  b");
+        editor.apply(Action::MoveWordLeft);
+        editor.apply(Action::Insert('\n'));
+        assert_eq!(input_string(&editor), "  a  \n  b");
+        assert_eq!(editor.cursor_pos(), 1);
+
+        editor.set_auto_indent(AutoIndent::Off);
+        type_str(&mut editor, "\n");
+        assert_eq!(input_string(&editor), "  a  \n  \nb");
+    }
+
+    #[test]
+    fn home_end_of_line() {
+        let mut editor = LineEditor::<4>::new();
+        editor.set_input("ab\ncde\nf");
+        editor.set_cursor_char_index(4);
+
+        editor.set_home_end(HomeEnd::Line);
+        editor.apply(Action::MoveHome);
+        assert_eq!(editor.cursor_char_index(), 3);
+        editor.apply(Action::MoveHome);
+        assert_eq!(editor.cursor_char_index(), 3);
+        editor.apply(Action::MoveEnd);
+        assert_eq!(editor.cursor_char_index(), 6);
+        editor.apply(Action::MoveEnd);
+        assert_eq!(editor.cursor_char_index(), 6);
+        editor.apply(Action::MoveRight);
+        editor.apply(Action::MoveEnd);
+        assert_eq!(editor.cursor_char_index(), 8);
+
+        editor.set_home_end(HomeEnd::Input);
+        editor.set_cursor_char_index(4);
+        editor.apply(Action::MoveHome);
+        assert_eq!(editor.cursor_char_index(), 0);
+        editor.apply(Action::MoveEnd);
+        assert_eq!(editor.cursor_char_index(), 8);
+    }
+
+    #[test]
+    fn move_between_lines_keeps_column() {
+        let mut editor = LineEditor::<4>::new();
+        editor.set_input("abcd\nx\nefgh");
+        editor.set_cursor_char_index(3);
+
+        assert!(editor.apply(Action::MoveLineDown));
+        assert_eq!(editor.cursor_char_index(), 6);
+        assert!(editor.apply(Action::MoveLineDown));
+        assert_eq!(editor.cursor_char_index(), 10);
+        assert!(!editor.apply(Action::MoveLineDown));
+        assert_eq!(editor.cursor_char_index(), 10);
+
+        assert!(editor.apply(Action::MoveLineUp));
+        assert_eq!(editor.cursor_char_index(), 6);
+        assert!(editor.apply(Action::MoveLineUp));
+        assert_eq!(editor.cursor_char_index(), 3);
+        assert!(!editor.apply(Action::MoveLineUp));
+
+        editor.apply(Action::MoveLeft);
+        editor.apply(Action::MoveLineDown);
+        editor.apply(Action::MoveLineDown);
+        assert_eq!(editor.cursor_char_index(), 9);
+    }
+
+    #[test]
+    fn left_right_wrap_at_line_edges() {
+        let mut editor = LineEditor::<4>::new();
+        editor.set_input("ab\ncd");
+        editor.set_cursor_char_index(3);
+        assert!(editor.line_edge_wrap());
+        editor.apply(Action::MoveLeft);
+        assert_eq!(editor.cursor_char_index(), 2);
+        editor.apply(Action::MoveRight);
+        assert_eq!(editor.cursor_char_index(), 3);
+
+        editor.set_line_edge_wrap(false);
+        editor.apply(Action::MoveLeft);
+        assert_eq!(editor.cursor_char_index(), 3);
+        editor.set_cursor_char_index(2);
+        editor.apply(Action::MoveRight);
+        assert_eq!(editor.cursor_char_index(), 2);
+        editor.apply(Action::MoveLeft);
+        assert_eq!(editor.cursor_char_index(), 1);
+    }
+
+    #[test]
+    fn insert_str_like_typing() {
+        let mut typed = LineEditor::<4>::new();
+        typed.set_input("ab");
+        typed.set_cursor_char_index(1);
+        let mut inserted = LineEditor::<4>::new();
+        inserted.set_input("ab");
+        inserted.set_cursor_char_index(1);
+
+        let text = "äö\nxyz";
+        for c in text.chars() {
+            typed.apply(Action::Insert(c));
+        }
+        inserted.insert_str(text);
+        assert_eq!(inserted.input(), typed.input());
+        assert_eq!(inserted.cursor_char_index(), typed.cursor_char_index());
+    }
+
+    #[test]
+    fn jump_to_matching_bracket() {
+        let mut editor = LineEditor::<4>::new();
+        editor.set_input("f(a[0], {b})");
+        editor.set_cursor_char_index(1);
+        assert!(editor.apply(Action::MoveMatchingBracket));
+        assert_eq!(editor.cursor_char_index(), 11);
+        assert!(editor.apply(Action::MoveMatchingBracket));
+        assert_eq!(editor.cursor_char_index(), 1);
+
+        // The bracket right before the cursor is used if there is none at it
+        editor.set_cursor_char_index(6);
+        assert!(editor.apply(Action::MoveMatchingBracket));
+        assert_eq!(editor.cursor_char_index(), 3);
+        editor.set_cursor_char_index(8);
+        assert!(editor.apply(Action::MoveMatchingBracket));
+        assert_eq!(editor.cursor_char_index(), 10);
+
+        editor.set_cursor_char_index(0);
+        assert!(!editor.apply(Action::MoveMatchingBracket));
+        assert_eq!(editor.cursor_char_index(), 0);
+    }
+}
@@ -0,0 +1,83 @@
+//! The error returned when running the repl
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+/// An error while running the repl, telling which part of it failed
+#[derive(Debug)]
+pub enum ReplError {
+    /// Enabling or disabling the raw mode of the terminal failed
+    RawMode(io::Error),
+    /// Entering or leaving the alternate screen failed
+    AlternateScreen(io::Error),
+    /// Drawing to the terminal failed
+    Draw(io::Error),
+    /// Reading terminal events failed
+    Events(io::Error),
+    /// The executor failed or its output could not be written to the output sink
+    Executor(io::Error),
+    /// Any other io error, e.g. writing terminal effects
+    Io(io::Error),
+}
+
+impl ReplError {
+    /// Get the underlying io error
+    pub fn io_error(&self) -> &io::Error {
+        match self {
+            ReplError::RawMode(e)
+            | ReplError::AlternateScreen(e)
+            | ReplError::Draw(e)
+            | ReplError::Events(e)
+            | ReplError::Executor(e)
+            | ReplError::Io(e) => e,
+        }
+    }
+
+    /// Convert into the underlying io error
+    pub fn into_io_error(self) -> io::Error {
+        match self {
+            ReplError::RawMode(e)
+            | ReplError::AlternateScreen(e)
+            | ReplError::Draw(e)
+            | ReplError::Events(e)
+            | ReplError::Executor(e)
+            | ReplError::Io(e) => e,
+        }
+    }
+}
+
+impl Display for ReplError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let context = match self {
+            ReplError::RawMode(_) => "failed to change the raw mode of the terminal",
+            ReplError::AlternateScreen(_) => "failed to enter or leave the alternate screen",
+            ReplError::Draw(_) => "failed to draw to the terminal",
+            ReplError::Events(_) => "failed to read terminal events",
+            ReplError::Executor(_) => "failed to execute a command",
+            ReplError::Io(_) => "io error",
+        };
+        // The io error is the source, error reporters print it after the context
+        f.write_str(context)
+    }
+}
+
+impl Error for ReplError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.io_error())
+    }
+}
+
+impl From<io::Error> for ReplError {
+    fn from(e: io::Error) -> Self {
+        ReplError::Io(e)
+    }
+}
+
+impl From<ReplError> for io::Error {
+    fn from(e: ReplError) -> Self {
+        e.into_io_error()
+    }
+}
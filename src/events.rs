@@ -0,0 +1,76 @@
+//! Sources of terminal events driving the repl
+
+use std::{io, time::Duration};
+
+use crossterm::event::{self, Event};
+
+/// A source of terminal events
+pub trait EventSource {
+    /// Wait up to `timeout` for an event, returns whether an event is available
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool>;
+
+    /// Read the next event, blocking until one is available
+    fn read(&mut self) -> io::Result<Event>;
+
+    /// Wait up to `timeout` for an event and read it, `None` if none arrived in time
+    fn poll_event(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        if self.poll(timeout)? {
+            self.read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Wait up to `timeout` for an event of the terminal, `None` if none arrived in time
+///
+/// This is meant for custom event loops feeding events to [crate::Repl::feed_event].
+pub fn poll_event(timeout: Duration) -> io::Result<Option<Event>> {
+    CrosstermEvents.poll_event(timeout)
+}
+
+/// The events of the terminal as read by crossterm
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CrosstermEvents;
+
+impl EventSource for CrosstermEvents {
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&mut self) -> io::Result<Event> {
+        event::read()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::*;
+
+    /// Returns each event after one poll timed out
+    struct Delayed(Vec<Event>, bool);
+
+    impl EventSource for Delayed {
+        fn poll(&mut self, _timeout: Duration) -> io::Result<bool> {
+            self.1 = !self.1;
+            Ok(!self.1 && !self.0.is_empty())
+        }
+
+        fn read(&mut self) -> io::Result<Event> {
+            Ok(self.0.remove(0))
+        }
+    }
+
+    #[test]
+    fn poll_event_times_out() {
+        let event = Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let mut events = Delayed(vec![event], false);
+        let timeout = Duration::from_millis(10);
+        assert_eq!(events.poll_event(timeout).unwrap(), None);
+        assert_eq!(events.poll_event(timeout).unwrap(), Some(event));
+        assert_eq!(events.poll_event(timeout).unwrap(), None);
+        assert_eq!(events.poll_event(timeout).unwrap(), None);
+    }
+}
@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+use tui::{style::Style, text::Span};
+
+/// A highlighter computing styles for parts of the current input
+pub trait Highlighter {
+    /// Get styled char index ranges of the input, later ranges take precedence on overlap
+    ///
+    /// Ranges exceeding the input are clamped to it.
+    fn highlight(&self, input: &[char]) -> Vec<(Range<usize>, Style)>;
+}
+
+impl<F: Fn(&[char]) -> Vec<(Range<usize>, Style)>> Highlighter for F {
+    fn highlight(&self, input: &[char]) -> Vec<(Range<usize>, Style)> {
+        self(input)
+    }
+}
+
+/// Build the spans of the input with the highlighted ranges patched onto the base style
+pub(crate) fn styled_spans(
+    input: &[char],
+    base: Style,
+    highlights: &[(Range<usize>, Style)],
+) -> Vec<Span<'static>> {
+    let mut styles = vec![base; input.len()];
+    for (range, style) in highlights {
+        let end = range.end.min(input.len());
+        let start = range.start.min(end);
+        for s in &mut styles[start..end] {
+            *s = base.patch(*style);
+        }
+    }
+
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for idx in 1..=input.len() {
+        if idx == input.len() || styles[idx] != styles[start] {
+            spans.push(Span::styled(
+                input[start..idx].iter().collect::<String>(),
+                styles[start],
+            ));
+            start = idx;
+        }
+    }
+
+    spans
+}
+
+/// Replace invisible chars of the input with visible substitutes, highlighted with `style`
+///
+/// Trailing spaces of every line become '·', tabs '→' and other control chars their symbol
+/// from the control pictures block. Line breaks are kept.
+pub(crate) fn show_whitespace(
+    input: &[char],
+    style: Style,
+) -> (Vec<char>, Vec<(Range<usize>, Style)>) {
+    let mut shown = input.to_vec();
+    let mut highlights = Vec::new();
+    let mut trailing = true;
+    for (idx, &c) in input.iter().enumerate().rev() {
+        let substitute = match c {
+            '\n' => {
+                trailing = true;
+                continue;
+            }
+            ' ' if trailing => Some('·'),
+            '\t' => Some('→'),
+            '\x7f' => Some('␡'),
+            c if (c as u32) < 0x20 => char::from_u32(0x2400 + c as u32),
+            _ => None,
+        };
+        trailing &= c.is_whitespace();
+        if let Some(substitute) = substitute {
+            shown[idx] = substitute;
+            highlights.push((idx..idx + 1, style));
+        }
+    }
+
+    (shown, highlights)
+}
+
+#[cfg(test)]
+mod test {
+    use tui::style::{Color, Modifier};
+
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn unhighlighted_is_one_span() {
+        let spans = styled_spans(&chars("echo"), Style::default(), &[]);
+        assert_eq!(spans, [Span::raw("echo")]);
+        assert!(styled_spans(&[], Style::default(), &[]).is_empty());
+    }
+
+    #[test]
+    fn overlapping_and_exceeding_ranges_clamped() {
+        let red = Style::default().fg(Color::Red);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let spans = styled_spans(&chars("abcdef"), bold, &[(1..4, red), (3..100, bold)]);
+        assert_eq!(
+            spans,
+            [
+                Span::styled("a", bold),
+                Span::styled("bc", bold.patch(red)),
+                Span::styled("def", bold),
+            ]
+        );
+    }
+
+    #[test]
+    fn whitespace_substituted() {
+        let dim = Style::default().add_modifier(Modifier::DIM);
+        let (shown, highlights) = show_whitespace(&chars("a \tb  \nc\x01 "), dim);
+        assert_eq!(shown, chars("a →b··\nc␁·"));
+        assert_eq!(
+            highlights.iter().map(|(r, _)| r.start).collect::<Vec<_>>(),
+            [9, 8, 5, 4, 2]
+        );
+    }
+}
@@ -1,14 +1,30 @@
-use std::{array, iter::Take, mem};
+use std::{
+    array,
+    cmp::Ordering,
+    fmt::{self, Debug, Formatter},
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    iter::Take,
+    mem,
+    path::Path,
+};
 
 // NOTE maybe at some point it makes sense to again work with String or some adapted version of it
 
 /// A structure storing a command history
-#[derive(Debug, PartialEq, PartialOrd)]
+///
+/// The entries are stored in a ring buffer, `head` is the slot of the oldest entry.
 pub struct History<const N: usize> {
     len: usize,
+    head: usize,
     /// Last read value, counted from the end, none if on a clean line
     cur: Option<usize>,
     stored_commands: [Vec<char>; N],
+    case_insensitive: bool,
+    /// The number of entries pushed so far, which is the number of the newest entry
+    pushed: usize,
+    on_evict: Option<Box<dyn FnMut(Vec<char>) + Send + Sync>>,
+    skip_blank: bool,
 }
 
 impl<const N: usize> History<N> {
@@ -26,31 +42,71 @@ impl<const N: usize> History<N> {
         let iter = initial.into_iter();
         for (slot, s) in me.stored_commands.iter_mut().zip(iter) {
             *slot = s;
+            me.len += 1;
         }
+        me.pushed = me.len;
 
         me
     }
 
+    /// Map a logical index, counted from the oldest entry, to the slot storing it
+    fn slot(&self, idx: usize) -> usize {
+        (self.head + idx) % N
+    }
+
     /// Push entry to the end of the history, removing the oldest entry if the capacity is reached
     ///
     /// This resets the current index which means that the next current returns the newest entry.
+    /// A history with a capacity of 0 stores nothing, this can be used to disable the history.
+    /// Empty commands and, unless disabled with [Self::set_skip_blank], commands made of
+    /// whitespace only are skipped. [crate::Repl::last_command] still returns them.
     pub fn push(&mut self, command: Vec<char>) {
-        if command.len() == 0 || command.iter().copied().all(char::is_whitespace) {
+        if N == 0
+            || command.is_empty()
+            || (self.skip_blank && command.iter().copied().all(char::is_whitespace))
+        {
             return;
         }
 
         self.cur = None;
+        self.pushed += 1;
         if self.len == N {
-            self.stored_commands.rotate_left(1);
-            self.stored_commands[N - 1] = command;
+            let evicted = mem::replace(&mut self.stored_commands[self.head], command);
+            self.head = self.slot(1);
+            if let Some(on_evict) = self.on_evict.as_mut() {
+                on_evict(evicted);
+            }
         } else if self.len < N {
-            self.stored_commands[self.len] = command;
+            let slot = self.slot(self.len);
+            self.stored_commands[slot] = command;
             self.len += 1;
         } else {
             unreachable!()
         }
     }
 
+    /// Set a callback receiving the oldest entry when [Self::push] removes it at capacity
+    ///
+    /// Entries removed by [Self::pop] or [Self::truncate] are not passed to it.
+    pub fn set_on_evict(&mut self, callback: impl FnMut(Vec<char>) + Send + Sync + 'static) {
+        self.on_evict = Some(Box::new(callback));
+    }
+
+    /// Remove the callback set with [Self::set_on_evict]
+    pub fn remove_on_evict(&mut self) {
+        self.on_evict = None;
+    }
+
+    /// Push all entries in order, only the newest [Self::CAPACITY] entries are kept
+    ///
+    /// Entries are filtered like in [Self::push] and the current index is reset.
+    pub fn extend(&mut self, commands: impl IntoIterator<Item = Vec<char>>) {
+        for command in commands {
+            self.push(command);
+        }
+        self.cur = None;
+    }
+
     /// Pop the newest entry of the history
     pub fn pop(&mut self) -> Option<Vec<char>> {
         self.cur = None;
@@ -59,19 +115,95 @@ impl<const N: usize> History<N> {
             None
         } else {
             self.len -= 1;
-            let val = mem::take(&mut self.stored_commands[self.len]);
+            self.pushed -= 1;
+            let slot = self.slot(self.len);
+            let val = mem::take(&mut self.stored_commands[slot]);
             Some(val)
         }
     }
 
+    /// Remove all but the newest `keep_newest` entries, does nothing if there are not more
+    ///
+    /// This resets the current index, the numbers of the kept entries don't change.
+    pub fn truncate(&mut self, keep_newest: usize) {
+        if keep_newest >= self.len {
+            return;
+        }
+
+        self.cur = None;
+        let dropped = self.len - keep_newest;
+        for idx in 0..dropped {
+            let slot = self.slot(idx);
+            self.stored_commands[slot] = Vec::new();
+        }
+        self.head = self.slot(dropped);
+        self.len = keep_newest;
+    }
+
+    /// Replace all entries with the newest [Self::CAPACITY] of the given ones, oldest first
+    ///
+    /// Unlike [Self::extend] no entries are skipped or passed to the eviction callback, the
+    /// entries are numbered from 1 again.
+    pub(crate) fn replace_entries(&mut self, entries: impl ExactSizeIterator<Item = Vec<char>>) {
+        let skip = entries.len().saturating_sub(N);
+        self.stored_commands = [(); N].map(|_| Vec::new());
+        self.head = 0;
+        self.cur = None;
+        self.len = 0;
+        for (slot, entry) in self.stored_commands.iter_mut().zip(entries.skip(skip)) {
+            *slot = entry;
+            self.len += 1;
+        }
+        self.pushed = self.len;
+    }
+
+    /// The number of entries currently stored
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no entries are stored
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The capacity of this History, the same as [Self::CAPACITY]
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
     pub fn get(&self, idx: usize) -> Option<&[char]> {
         if idx >= self.len {
             None
         } else {
-            self.stored_commands.get(idx).map(|s| s.as_ref())
+            self.stored_commands.get(self.slot(idx)).map(|s| s.as_ref())
         }
     }
 
+    /// Get the session number of the entry at `idx`, counted from the oldest entry
+    ///
+    /// Entries are numbered from 1 in the order they were pushed, like in the history of bash.
+    /// The numbers are kept when old entries are removed to make room for new ones.
+    pub fn entry_number(&self, idx: usize) -> Option<usize> {
+        (idx < self.len).then(|| self.pushed - self.len + idx + 1)
+    }
+
+    /// Expand a history reference like in bash, `!!` is the newest entry and `!n` the entry
+    /// with [number](Self::entry_number) `n`
+    ///
+    /// Returns `None` if the token is no history reference or the entry is not stored anymore.
+    pub fn expand(&self, token: &str) -> Option<Vec<char>> {
+        let entry = match token.strip_prefix('!')? {
+            "!" => self.newest(),
+            number => {
+                let number = number.parse::<usize>().ok()?;
+                let first = self.pushed - self.len + 1;
+                number.checked_sub(first).and_then(|idx| self.get(idx))
+            }
+        };
+        entry.map(<[char]>::to_vec)
+    }
+
     /// Get a reference to the newest element
     pub fn newest(&self) -> Option<&[char]> {
         if self.len == 0 {
@@ -95,6 +227,27 @@ impl<const N: usize> History<N> {
         }
     }
 
+    /// Get the index of the current element, counted from the oldest entry
+    pub fn current_index(&self) -> Option<usize> {
+        self.cur.and_then(|cur| self.len.checked_sub(cur + 1))
+    }
+
+    /// Move the current pointer to the entry at `idx`, counted from the oldest entry
+    ///
+    /// Indices past the newest entry are clamped to it, [Self::prev] and [Self::next] continue
+    /// from the entry.
+    pub fn seek(&mut self, idx: usize) {
+        self.cur = self
+            .len
+            .checked_sub(1)
+            .map(|newest| newest - idx.min(newest));
+    }
+
+    /// Move the current pointer back to the clean line after the newest entry
+    pub fn reset_current(&mut self) {
+        self.cur = None;
+    }
+
     /// Get the previous element and move the current pointer to the previous element
     pub fn prev(&mut self) -> Option<&[char]> {
         if let Some(cur) = self.cur.as_mut() {
@@ -108,7 +261,8 @@ impl<const N: usize> History<N> {
         self.current()
     }
 
-    /// Get the previous element and move the current pointer to the previous element
+    /// Get the next element and move the current pointer to the next element
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<&[char]> {
         if let Some(0) = self.cur {
             self.cur = None;
@@ -123,33 +277,238 @@ impl<const N: usize> History<N> {
         }
     }
 
+    /// Whether [Self::push] skips commands made of whitespace only
+    pub fn skip_blank(&self) -> bool {
+        self.skip_blank
+    }
+
+    /// Set whether [Self::push] skips commands made of whitespace only, it does by default
+    ///
+    /// This is for languages where whitespace is meaningful. Empty commands are always skipped.
+    pub fn set_skip_blank(&mut self, skip_blank: bool) {
+        self.skip_blank = skip_blank;
+    }
+
+    /// Whether [Self::search] ignores case
+    pub fn case_insensitive_search(&self) -> bool {
+        self.case_insensitive
+    }
+
+    /// Set whether [Self::search] ignores case, it is case sensitive by default
+    pub fn set_case_insensitive_search(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+    }
+
+    /// Get the index of the newest entry containing `query`
+    pub fn search(&self, query: &[char]) -> Option<usize> {
+        self.search_iter(query).next()
+    }
+
+    /// Get the indices of all entries containing `query`, starting with the newest
+    pub fn search_iter<'a>(&'a self, query: &[char]) -> impl Iterator<Item = usize> + 'a {
+        let query = self.search_key(query);
+        (0..self.len).rev().filter(move |&idx| {
+            let entry = self.search_key(&self.stored_commands[self.slot(idx)]);
+            query.is_empty() || entry.windows(query.len()).any(|w| w == query.as_slice())
+        })
+    }
+
+    /// Lowercase the text if searching is case insensitive
+    ///
+    /// Lowercasing a char can produce multiple chars, so this can't be done char by char while
+    /// comparing.
+    fn search_key(&self, text: &[char]) -> Vec<char> {
+        if self.case_insensitive {
+            text.iter().copied().flat_map(char::to_lowercase).collect()
+        } else {
+            text.to_vec()
+        }
+    }
+
+    /// Get the entries of this history which are not in `other`, oldest first
+    ///
+    /// Entries are compared by content, so it doesn't matter where they are in either history.
+    pub fn diff(&self, other: &History<N>) -> Vec<&[char]> {
+        self.iter()
+            .filter(|entry| !other.iter().any(|e| e == *entry))
+            .collect()
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &[char]> {
-        self.stored_commands
-            .iter()
-            .map(|s| s.as_slice())
-            .take(self.len)
+        (0..self.len).map(|idx| self.stored_commands[self.slot(idx)].as_slice())
+    }
+
+    /// Iterate over the entries as strings, oldest first
+    pub fn iter_strings(&self) -> impl Iterator<Item = String> + '_ {
+        self.iter().map(|entry| entry.iter().collect())
+    }
+
+    /// Read a history written by [Self::save] or [write_entry], keeping the newest entries
+    ///
+    /// Files without a [header](write_header) are read as one plain command per line, the format
+    /// before line breaks were escaped.
+    pub fn load(reader: impl BufRead) -> io::Result<Self> {
+        let mut history = Self::new();
+        let mut lines = reader.lines();
+        let Some(first) = lines.next().transpose()? else {
+            return Ok(history);
+        };
+
+        if check_header(&first)? {
+            for line in lines {
+                history.push(unescape(&line?));
+            }
+        } else {
+            history.push(first.chars().collect());
+            for line in lines {
+                history.push(line?.chars().collect());
+            }
+        }
+
+        Ok(history)
+    }
+
+    /// Read a history file, see [Self::load]
+    pub fn load_from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::load(BufReader::new(File::open(path)?))
+    }
+
+    /// Write a [header](write_header) and all entries, oldest first, one per line
+    pub fn save(&self, mut out: impl Write) -> io::Result<()> {
+        write_header(&mut out)?;
+        for entry in self.iter() {
+            write_entry(&mut out, entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The start of the first line of a history file, followed by the version of its format
+const HEADER: &str = "#tui_repl history v";
+
+/// The version of the format written by [History::save]
+const VERSION: u32 = 1;
+
+/// Write the first line of a history file, telling the version of its format
+pub fn write_header(out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "{}{}", HEADER, VERSION)
+}
+
+/// Check whether the first line of a history file is a [header](write_header)
+///
+/// Files without a header are of the format before line breaks were escaped. Headers of other
+/// versions than the one written by [write_header] are an error, the file can't be read or
+/// appended to.
+pub fn check_header(line: &str) -> io::Result<bool> {
+    match line.trim_end_matches(['\r', '\n']).strip_prefix(HEADER) {
+        Some(version) if version.parse() == Ok(VERSION) => Ok(true),
+        Some(version) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported history file version {}", version),
+        )),
+        None => Ok(false),
+    }
+}
+
+/// Write a single entry as a line like [History::save], e.g. to append it to a history file
+///
+/// A new file has to start with a [header](write_header).
+/// Line breaks in the entry are escaped as `\n` and backslashes as `\\`.
+pub fn write_entry(out: &mut impl Write, entry: &[char]) -> io::Result<()> {
+    let mut line = String::with_capacity(entry.len() + 1);
+    for &c in entry {
+        match c {
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            c => line.push(c),
+        }
     }
+    line.push('\n');
+    out.write_all(line.as_bytes())
+}
+
+fn unescape(line: &str) -> Vec<char> {
+    let mut entry = Vec::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                chars.next();
+                entry.push('\n');
+            }
+            ('\\', Some('\\')) => {
+                chars.next();
+                entry.push('\\');
+            }
+            (c, _) => entry.push(c),
+        }
+    }
+
+    entry
 }
 
 impl<const N: usize> IntoIterator for History<N> {
     type Item = Vec<char>;
     type IntoIter = Take<array::IntoIter<Vec<char>, N>>;
 
-    fn into_iter(self) -> Self::IntoIter {
+    fn into_iter(mut self) -> Self::IntoIter {
+        if N != 0 {
+            self.stored_commands.rotate_left(self.head);
+        }
         self.stored_commands.into_iter().take(self.len)
     }
 }
 
+impl<const N: usize> PartialEq for History<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cur == other.cur && self.iter().eq(other.iter())
+    }
+}
+
+/// Orders like the derived implementation did before the entries were stored in a ring buffer,
+/// by the number of entries, then the navigation position and then the entries
+impl<const N: usize> PartialOrd for History<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(
+            self.len
+                .cmp(&other.len)
+                .then_with(|| self.cur.cmp(&other.cur))
+                .then_with(|| self.iter().cmp(other.iter())),
+        )
+    }
+}
+
 impl<const N: usize> Default for History<N> {
     fn default() -> Self {
         Self {
             cur: None,
             len: 0,
+            head: 0,
+            case_insensitive: false,
+            pushed: 0,
             stored_commands: [(); N].map(|_| Vec::new()),
+            on_evict: None,
+            skip_blank: true,
         }
     }
 }
 
+impl<const N: usize> Debug for History<N> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("History")
+            .field("len", &self.len)
+            .field("head", &self.head)
+            .field("cur", &self.cur)
+            .field("stored_commands", &self.stored_commands)
+            .field("case_insensitive", &self.case_insensitive)
+            .field("pushed", &self.pushed)
+            .field("on_evict", &self.on_evict.is_some())
+            .field("skip_blank", &self.skip_blank)
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -180,6 +539,20 @@ mod test {
         assert_eq!(history.pop(), Some("Hello".to_char_vec()));
     }
 
+    #[test]
+    fn blank_skip_configurable() {
+        let mut history = History::<32>::new();
+        history.push("  ".to_char_vec());
+        history.push("\t\n".to_char_vec());
+        assert!(history.is_empty());
+
+        history.set_skip_blank(false);
+        history.push("  ".to_char_vec());
+        history.push("".to_char_vec());
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.newest(), Some(&[' ', ' '][..]));
+    }
+
     #[test]
     fn contains_all() {
         let mut history = History::<32>::new();
@@ -188,7 +561,11 @@ mod test {
         history.push("!".to_char_vec());
         assert_eq!(
             &history.into_iter().collect::<Vec<_>>(),
-            &["Hello".to_char_vec(), "World".to_char_vec(), "!".to_char_vec()]
+            &[
+                "Hello".to_char_vec(),
+                "World".to_char_vec(),
+                "!".to_char_vec()
+            ]
         )
     }
 
@@ -204,6 +581,127 @@ mod test {
         )
     }
 
+    #[test]
+    fn len_tracks_push_pop() {
+        let mut history = History::<2>::new();
+        assert!(history.is_empty());
+        assert_eq!(history.capacity(), 2);
+        history.push("Hello".to_char_vec());
+        assert_eq!(history.len(), 1);
+        history.push("World".to_char_vec());
+        history.push("!".to_char_vec());
+        assert_eq!(history.len(), 2);
+        history.pop();
+        assert_eq!(history.len(), 1);
+        history.pop();
+        history.pop();
+        assert_eq!(history.len(), 0);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn wraps_around_ring() {
+        let mut history = History::<3>::new();
+        for s in ["a", "b", "c", "d", "e"] {
+            history.push(s.to_char_vec());
+        }
+        assert_eq!(history.get(0), Some("c".to_char_vec().as_slice()));
+        assert_eq!(history.newest(), Some("e".to_char_vec().as_slice()));
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            ["c", "d", "e"].map(|s| s.to_char_vec())
+        );
+        assert_eq!(history.prev(), Some("e".to_char_vec().as_slice()));
+        assert_eq!(history.prev(), Some("d".to_char_vec().as_slice()));
+        assert_eq!(history.pop(), Some("e".to_char_vec()));
+        history.push("f".to_char_vec());
+        assert_eq!(
+            history.into_iter().collect::<Vec<_>>(),
+            ["c", "d", "f"].map(|s| s.to_char_vec())
+        );
+    }
+
+    #[test]
+    fn with_initial_counts_entries() {
+        let history = History::<2>::with_initial(["a", "b", "c"].map(|s| s.to_char_vec()));
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            ["a", "b"].map(|s| s.to_char_vec())
+        );
+    }
+
+    #[test]
+    fn ordered_by_len_first() {
+        let short = History::<4>::with_initial(["b"].map(|s| s.to_char_vec()));
+        let long = History::<4>::with_initial(["a", "a"].map(|s| s.to_char_vec()));
+        assert!(short < long);
+
+        let mut navigated = History::<4>::with_initial(["a"].map(|s| s.to_char_vec()));
+        navigated.prev();
+        assert!(History::<4>::with_initial(["b"].map(|s| s.to_char_vec())) < navigated);
+    }
+
+    #[test]
+    fn eq_ignores_ring_position() {
+        let mut history = History::<2>::new();
+        for s in ["a", "b", "c"] {
+            history.push(s.to_char_vec());
+        }
+        let other = History::<2>::with_initial(["b", "c"].map(|s| s.to_char_vec()));
+        assert_eq!(history, other);
+    }
+
+    #[test]
+    fn extend_past_capacity() {
+        let mut history = History::<3>::new();
+        history.push("a".to_char_vec());
+        history.prev();
+        history.extend(["b", " ", "c", "d"].map(|s| s.to_char_vec()));
+        assert_eq!(history.current(), None);
+        assert_eq!(
+            history.into_iter().collect::<Vec<_>>(),
+            ["b", "c", "d"].map(|s| s.to_char_vec())
+        );
+    }
+
+    #[test]
+    fn search_case_sensitivity() {
+        let mut history = History::<8>::new();
+        history.extend(["GIT push", "git status", "İstanbul", "ls"].map(|s| s.to_char_vec()));
+        assert_eq!(history.search(&"git".to_char_vec()), Some(1));
+        assert_eq!(history.search(&"GIT".to_char_vec()), Some(0));
+        assert_eq!(history.search(&"push".to_char_vec()), Some(0));
+        assert_eq!(history.search(&"istanbul".to_char_vec()), None);
+
+        history.set_case_insensitive_search(true);
+        assert_eq!(
+            history
+                .search_iter(&"Git".to_char_vec())
+                .collect::<Vec<_>>(),
+            [1, 0]
+        );
+        assert_eq!(history.search(&"i̇stanbul".to_char_vec()), Some(2));
+        assert_eq!(history.search(&"İST".to_char_vec()), Some(2));
+        assert_eq!(history.search(&"svn".to_char_vec()), None);
+    }
+
+    #[test]
+    fn diff_unique_entries() {
+        let ours = History::<4>::with_initial(["a", "b", "c", "d"].map(|s| s.to_char_vec()));
+        let mut theirs = History::<4>::new();
+        theirs.extend(["x", "c", "a"].map(|s| s.to_char_vec()));
+        assert_eq!(
+            ours.diff(&theirs),
+            ["b", "d"]
+                .map(|s| s.to_char_vec())
+                .each_ref()
+                .map(|v| v.as_slice())
+        );
+        assert_eq!(theirs.diff(&ours), ["x".to_char_vec().as_slice()]);
+        assert!(ours.diff(&ours).is_empty());
+    }
+
     #[test]
     fn navigating0() {
         let mut history = History::<32>::new();
@@ -218,7 +716,9 @@ mod test {
         assert_eq!(history.prev(), Some("Hello".to_char_vec().as_slice()));
         assert_eq!(history.current(), Some("Hello".to_char_vec().as_slice()));
         assert_eq!(history.next(), Some("World".to_char_vec().as_slice()));
+        assert_eq!(history.current_index(), Some(1));
         assert_eq!(history.next(), None);
+        assert_eq!(history.current_index(), None);
     }
 
     #[test]
@@ -228,4 +728,200 @@ mod test {
         assert_eq!(history.prev(), Some("Hello".to_char_vec().as_slice()));
         assert_eq!(history.next(), None);
     }
+
+    #[test]
+    fn entry_numbers_survive_eviction() {
+        let mut history = History::<3>::with_initial(["a", "b"].map(ToCharVec::to_char_vec));
+        assert_eq!(history.entry_number(0), Some(1));
+        assert_eq!(history.entry_number(1), Some(2));
+        assert_eq!(history.entry_number(2), None);
+
+        for s in ["c", "d", "e"] {
+            history.push(s.to_char_vec());
+        }
+        assert_eq!(history.get(0), Some("c".to_char_vec().as_slice()));
+        assert_eq!(
+            (0..4)
+                .map(|idx| history.entry_number(idx))
+                .collect::<Vec<_>>(),
+            [Some(3), Some(4), Some(5), None]
+        );
+
+        history.push(" ".to_char_vec());
+        history.pop();
+        history.push("f".to_char_vec());
+        assert_eq!(history.entry_number(2), Some(5));
+    }
+
+    #[test]
+    fn expand_references() {
+        let mut history = History::<3>::new();
+        assert_eq!(history.expand("!!"), None);
+        for s in ["a", "b", "c", "d"] {
+            history.push(s.to_char_vec());
+        }
+
+        assert_eq!(history.expand("!!"), Some("d".to_char_vec()));
+        assert_eq!(history.expand("!3"), Some("c".to_char_vec()));
+        assert_eq!(history.expand("!2"), Some("b".to_char_vec()));
+        assert_eq!(history.expand("!1"), None);
+        assert_eq!(history.expand("!5"), None);
+        assert_eq!(history.expand("!x"), None);
+        assert_eq!(history.expand("ls"), None);
+    }
+
+    #[test]
+    fn iter_strings_matches_pushed() {
+        let mut history = History::<2>::new();
+        for s in ["ls", "echo hi", "exit"] {
+            history.push(s.to_char_vec());
+        }
+        assert_eq!(
+            history.iter_strings().collect::<Vec<_>>(),
+            ["echo hi", "exit"]
+        );
+    }
+
+    #[test]
+    fn zero_capacity_stores_nothing() {
+        let mut history = History::<0>::with_initial(["a"].map(ToCharVec::to_char_vec));
+        history.push("ls".to_char_vec());
+        history.extend(["b", "c"].map(ToCharVec::to_char_vec));
+
+        assert!(history.is_empty());
+        assert_eq!(history.iter().count(), 0);
+        assert_eq!(history.prev(), None);
+        assert_eq!(history.prev(), None);
+        assert_eq!(history.next(), None);
+        assert_eq!(history.pop(), None);
+        assert_eq!(history.search(&[]), None);
+        assert_eq!(history.expand("!!"), None);
+        assert_eq!(history.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn truncate_keeps_newest() {
+        let mut history = History::<4>::new();
+        for s in ["a", "b", "c", "d", "e"] {
+            history.push(s.to_char_vec());
+        }
+        history.prev();
+
+        history.truncate(2);
+        assert_eq!(history.iter_strings().collect::<Vec<_>>(), ["d", "e"]);
+        assert_eq!(history.current(), None);
+        assert_eq!(history.entry_number(0), Some(4));
+        assert_eq!(
+            history
+                .stored_commands
+                .iter()
+                .filter(|s| s.is_empty())
+                .count(),
+            2
+        );
+
+        history.truncate(5);
+        assert_eq!(history.len(), 2);
+        history.push("f".to_char_vec());
+        assert_eq!(history.iter_strings().collect::<Vec<_>>(), ["d", "e", "f"]);
+        history.truncate(0);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn reset_current_returns_to_clean_line() {
+        let mut history = History::<3>::with_initial(["a", "b"].map(ToCharVec::to_char_vec));
+        history.prev();
+        history.prev();
+        history.reset_current();
+        assert_eq!(history.current(), None);
+        assert_eq!(history.prev(), Some("b".to_char_vec().as_slice()));
+    }
+
+    #[test]
+    fn save_and_load_escapes_line_breaks() {
+        let history =
+            History::<2>::with_initial(["old", "a\\nb\nc", "\\"].map(ToCharVec::to_char_vec));
+        let mut saved = Vec::new();
+        history.save(&mut saved).unwrap();
+        assert_eq!(saved, b"#tui_repl history v1\nold\na\\\\nb\\nc\n");
+
+        let mut saved = Vec::new();
+        write_header(&mut saved).unwrap();
+        write_entry(&mut saved, &"first".to_char_vec()).unwrap();
+        write_entry(&mut saved, &"a\\nb\nc".to_char_vec()).unwrap();
+        write_entry(&mut saved, &"\\".to_char_vec()).unwrap();
+        let loaded = History::<2>::load(saved.as_slice()).unwrap();
+        assert_eq!(
+            loaded.iter_strings().collect::<Vec<_>>(),
+            ["a\\nb\nc", "\\"]
+        );
+    }
+
+    #[test]
+    fn load_legacy_and_versioned_files() {
+        let legacy = History::<4>::load("ls\\n\necho a\n".as_bytes()).unwrap();
+        assert_eq!(
+            legacy.iter_strings().collect::<Vec<_>>(),
+            ["ls\\n", "echo a"]
+        );
+
+        let versioned = "#tui_repl history v1\nls\\n\necho a\n";
+        let versioned = History::<4>::load(versioned.as_bytes()).unwrap();
+        assert_eq!(
+            versioned.iter_strings().collect::<Vec<_>>(),
+            ["ls\n", "echo a"]
+        );
+
+        assert!(History::<4>::load("".as_bytes()).unwrap().is_empty());
+        let err = History::<4>::load("#tui_repl history v2\nls\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn seek_to_entry() {
+        let mut history =
+            History::<4>::with_initial(["a", "b", "c", "d"].map(ToCharVec::to_char_vec));
+        history.seek(1);
+        assert_eq!(history.current_index(), Some(1));
+        assert_eq!(history.current(), Some("b".to_char_vec().as_slice()));
+        assert_eq!(history.prev(), Some("a".to_char_vec().as_slice()));
+        history.seek(2);
+        assert_eq!(history.next(), Some("d".to_char_vec().as_slice()));
+
+        history.seek(10);
+        assert_eq!(history.current(), Some("d".to_char_vec().as_slice()));
+        assert_eq!(history.next(), None);
+
+        let mut empty = History::<4>::new();
+        empty.seek(0);
+        assert_eq!(empty.current(), None);
+    }
+
+    #[test]
+    fn evicted_entries_passed_to_callback() {
+        let evicted = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut history = History::<2>::new();
+        let sink = evicted.clone();
+        history.set_on_evict(move |entry| sink.lock().unwrap().push(entry));
+
+        history.extend(["a", "b", "c", "d"].map(ToCharVec::to_char_vec));
+        history.pop();
+        history.push("e".to_char_vec());
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            ["a", "b"].map(ToCharVec::to_char_vec)
+        );
+        assert_eq!(history.iter_strings().collect::<Vec<_>>(), ["c", "e"]);
+
+        history.remove_on_evict();
+        history.push("f".to_char_vec());
+        assert_eq!(evicted.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn history_is_send_and_sync() {
+        fn is_send_and_sync<T: Send + Sync>() {}
+        is_send_and_sync::<History<32>>();
+    }
 }
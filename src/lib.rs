@@ -1,41 +1,405 @@
+pub mod bracket;
+
+pub mod complete;
+
+pub mod editor;
+
+pub mod error;
+
+pub mod events;
+
+pub mod highlight;
+
 pub mod history;
 
+pub mod suggest;
+
 pub mod util;
 
+pub mod validate;
+
+pub mod word;
+
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     fmt::{self, Debug, Formatter},
-    io,
-    ops::ControlFlow,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, IsTerminal},
+    iter, mem,
+    ops::{ControlFlow, Deref, Range},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
 };
 
 use crossterm::{
-    event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
-    },
+    cursor::{CursorShape, Show},
+    event::{DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers},
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
 };
 use tui::{
     backend::{Backend, CrosstermBackend},
     buffer::Buffer,
     layout::Rect,
-    widgets::{Paragraph, Widget},
+    style::{Modifier, Style},
+    text::{Span, Spans, Text},
+    widgets::{Paragraph, StatefulWidget, Widget},
     Terminal,
 };
 
+use complete::{Completer, CompletionMenu};
+use editor::{Action, AutoIndent, HistoryCursor, HomeEnd, LineEditor};
+use error::ReplError;
+use events::{CrosstermEvents, EventSource};
+use highlight::Highlighter;
 use history::History;
-
-// TODO add manual scrolling support
-// TODO add removing complete words with ctrl + backspace/ctrl + del
+use validate::Validator;
+use word::WordClass;
 
 // TODO termion support
 // TODO maybe optimize to copy less text around?
 
+/// How many edits of the input can be undone
+pub const UNDO_LIMIT: usize = 256;
+
+/// How long the event loop waits for an event before checking for redraw requests
+pub const TICK_RATE: Duration = Duration::from_millis(50);
+
+/// What happens to line breaks in text passed to [Repl::feed_paste]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PastePolicy {
+    /// Every complete line is submitted, the text after the last line break stays in the input
+    ///
+    /// This behaves as if the text was typed.
+    #[default]
+    SubmitCompleteLines,
+    /// All lines are submitted if the text ends with a line break, otherwise nothing is
+    SubmitOnFinalNewline,
+    /// Nothing is submitted, line breaks are inserted into the input
+    NeverSubmit,
+}
+
+/// How carriage returns in pasted and typed input are handled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NewlinePolicy {
+    /// "\r\n" and lone '\r' become '\n'
+    #[default]
+    Normalize,
+    /// Carriage returns are inserted as they are
+    Keep,
+}
+
+/// Where the prompt is placed if the text doesn't end with a line break
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PromptLine {
+    /// The prompt continues the last line of the output, like in a terminal
+    #[default]
+    ContinueOutput,
+    /// The prompt always starts on a line of its own
+    NewLine,
+}
+
+/// What happens when an exit key is pressed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExitPolicy {
+    /// Always exit
+    #[default]
+    Always,
+    /// Ring the bell instead of exiting while the input [is dirty](Repl::is_dirty)
+    RefuseWhenDirty,
+    /// While the input is dirty an exit key has to be pressed twice in a row to exit
+    ///
+    /// The first press rings the bell and sets [Repl::exit_pending].
+    ConfirmWhenDirty,
+}
+
+/// What Ctrl+D does, the other exit keys always follow the [ExitPolicy]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CtrlDPolicy {
+    /// Exit like the other exit keys
+    #[default]
+    Exit,
+    /// Exit only if the input is empty, ring the bell otherwise
+    ExitWhenEmpty,
+    /// Exit if the input is empty, delete the char after the cursor otherwise like readline
+    DeleteOrExit,
+}
+
+/// Which modified Enter key inserts a line break into the input instead of submitting it
+///
+/// Many terminals send Ctrl+Enter as a plain Enter, Alt+Enter is delivered more reliably.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NewlineKey {
+    /// Ctrl+Enter and Alt+Enter
+    #[default]
+    Both,
+    /// Only Ctrl+Enter
+    CtrlEnter,
+    /// Only Alt+Enter
+    AltEnter,
+    /// Neither, modified Enter keys are ignored
+    Disabled,
+}
+
+/// Which Enter key submits the input
+///
+/// If it is not the plain Enter key, plain Enter inserts a line break instead, like in chat
+/// applications.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SubmitKey {
+    /// Enter and Shift+Enter
+    #[default]
+    Enter,
+    /// Ctrl+Enter
+    CtrlEnter,
+    /// Alt+Enter
+    AltEnter,
+}
+
+/// What happens when an empty input is submitted
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EmptySubmitPolicy {
+    /// The empty command is executed like any other and the prompt is emitted
+    #[default]
+    Execute,
+    /// Nothing happens
+    Ignore,
+    /// A line break and the prompt are written to the text, the executor is not called
+    EmitNewline,
+}
+
+/// How lines of the text wider than the repl are displayed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// The line is cut off at the edge
+    #[default]
+    Clip,
+    /// The line is cut off and its last visible char is replaced by the marker, e.g. '…'
+    ///
+    /// The line the input continues is always clipped, so that the cursor stays in place.
+    Truncate(char),
+}
+
+/// The shape of the cursor and whether it blinks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+/// Where a submitted command and its output are in the text, as byte indices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BlockMark {
+    /// The start of the echoed prompt
+    start: usize,
+    /// The start of the echoed command
+    command: usize,
+    /// The start of the output, which lasts until the next block
+    output: usize,
+    folded: bool,
+}
+
+/// A submitted command and the output following it in the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandBlock<'a> {
+    /// The prompt in front of the command
+    pub prompt: &'a str,
+    /// The command as it was echoed, empty if it wasn't
+    pub command: &'a str,
+    /// Everything written after the command until the next one, including the next prompt if
+    /// an executor wrote it
+    pub output: &'a str,
+    /// Whether the output is folded
+    pub folded: bool,
+}
+
+/// A line drawn across the repl between the text and the input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Separator {
+    pub symbol: char,
+    pub style: Style,
+}
+
+/// How the bracket at the cursor and the one matching it are highlighted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BracketHighlight {
+    /// The style of both brackets of a matched pair
+    pub matched: Style,
+    /// The style of a bracket without a match, `None` to leave it as it is
+    pub unmatched: Option<Style>,
+}
+
+/// Where the prompt and input are placed relative to the text
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputPosition {
+    /// Below the text, continuing its last line like in a terminal
+    #[default]
+    Bottom,
+    /// In the first rows, the text follows below them
+    Top,
+}
+
+/// The rendered lines of the text, reused while the text and the area don't change
+#[derive(Debug)]
+struct TextCache {
+    area: Rect,
+    generation: u64,
+    lines: Vec<Spans<'static>>,
+    /// Width of the line number gutter, 0 if it is hidden
+    gutter: u16,
+    /// Line number of the first cached line
+    first_line: usize,
+}
+
+/// The file submitted commands are appended to and the commands not yet written to it
+#[derive(Debug)]
+struct HistoryFile {
+    path: PathBuf,
+    pending: Vec<Vec<char>>,
+    /// The error of the last failed write after a submit, reset by a successful one
+    error: Option<io::Error>,
+}
+
+/// What rendering a [Repl] keeps between frames
+///
+/// Used when rendering the repl as a [StatefulWidget], which only needs a shared reference to it.
+/// Rendering `&mut Repl` as a [Widget] keeps its own state inside the repl.
+#[derive(Debug, Default)]
+pub struct ReplState {
+    text_cache: Option<TextCache>,
+    busy_frame: usize,
+}
+
+/// The text as it is shown, see [Repl::shown_text]
+enum ShownText<'a> {
+    /// Nothing is folded, the text is shown as it is
+    Text(&'a str),
+    /// The text with the output of folded commands hidden
+    Folded(Arc<str>),
+}
+
+impl Deref for ShownText<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Self::Text(text) => text,
+            Self::Folded(text) => text,
+        }
+    }
+}
+
+/// The spinner shown while the repl is busy, if no other frames are set
+const DEFAULT_BUSY_FRAMES: [&str; 4] = ["|", "/", "-", "\\"];
+
+/// Resets the shape of the cursor to the default of the terminal
+const RESET_CURSOR_SHAPE: &[u8] = b"\x1b[0 q";
+
+/// Callback computing the prompt for the next input line
+pub type PromptProvider<const HISTORY_SIZE: usize> =
+    Box<dyn FnMut(&Repl<HISTORY_SIZE>) -> String + Send + Sync>;
+
+/// Callback invoked with the new input whenever a key event changed the input
+pub type InputChangeCallback = Box<dyn FnMut(&[char]) + Send + Sync>;
+
+/// The state of a session in plain data, e.g. to save it and continue it later
+///
+/// Created with [Repl::snapshot] and applied with [Repl::restore]. Callbacks and settings are
+/// not part of it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReplSnapshot {
+    /// All output, including the echoed commands
+    pub text: String,
+    /// The prompt shown for every new input
+    pub prompt: String,
+    /// The prompt in front of the current input
+    pub active_prompt: String,
+    /// The current input
+    pub input: String,
+    /// The char index of the cursor in the input
+    pub cursor: usize,
+    /// The entries of the history, oldest first
+    pub history: Vec<String>,
+}
+
 #[derive(Default)]
 pub struct Repl<const HISTORY_SIZE: usize> {
-    current_input: Vec<char>,
-    cursor_pos: u16,
-    history: History<HISTORY_SIZE>,
+    editor: LineEditor<HISTORY_SIZE>,
     text: String,
+    /// The submitted commands in the text, oldest first
+    blocks: Vec<BlockMark>,
+    /// The text with folded output hidden and the text generation it was built for
+    shown_cache: Mutex<Option<(u64, Arc<str>)>>,
+    prompt: String,
+    /// The prompt in front of the current input, it is moved to the text on submit
+    active_prompt: String,
+    ensure_newline: bool,
+    missing_newline_marker: Option<String>,
+    prompt_line: PromptLine,
+    input_style: Style,
+    aliases: HashMap<String, String>,
+    store_expanded_aliases: bool,
+    /// Inverted so that the derived default echoes submitted commands
+    no_command_echo: bool,
+    on_input_change: Option<InputChangeCallback>,
+    highlighter: Option<Box<dyn Highlighter + Send + Sync>>,
+    show_whitespace: bool,
+    show_line_numbers: bool,
+    /// Inverted so that the derived default echoes the input
+    no_echo: bool,
+    overflow: Overflow,
+    paging: bool,
+    held_output: String,
+    page_height: u16,
+    scroll_offset: usize,
+    read_only: bool,
+    newline_key: NewlineKey,
+    submit_key: SubmitKey,
+    output_sink: Option<Box<dyn io::Write + Send + Sync>>,
+    state: ReplState,
+    text_generation: u64,
+    output_channel: Option<(Sender<String>, Mutex<Receiver<String>>)>,
+    redraw: RedrawHandle,
+    scrollback_limit: Option<usize>,
+    paste_policy: PastePolicy,
+    newline_policy: NewlinePolicy,
+    allow_control_chars: bool,
+    empty_submit_policy: EmptySubmitPolicy,
+    prompt_provider: Option<PromptProvider<HISTORY_SIZE>>,
+    bell: bool,
+    exit_policy: ExitPolicy,
+    ctrl_d_policy: CtrlDPolicy,
+    exit_pending: bool,
+    interrupted: bool,
+    last_command: Option<Vec<char>>,
+    skip_repeat_history: bool,
+    repeat_key: bool,
+    cursor_style: Option<CursorStyle>,
+    cursor_style_changed: bool,
+    history_file: Option<HistoryFile>,
+    history_flush_interval: usize,
+    busy: Arc<AtomicBool>,
+    /// Set while the executor runs, kept apart from the flag of [Self::set_busy]
+    executing: Arc<AtomicBool>,
+    busy_frames: Vec<String>,
+    /// Inverted so that the derived default is focused
+    unfocused: bool,
+    input_position: InputPosition,
+    separator: Option<Separator>,
+    bracket_highlight: Option<BracketHighlight>,
+    validator: Option<Box<dyn Validator + Send + Sync>>,
+    validation_error: Option<String>,
+    /// Whether the input may have changed since it was last validated
+    validation_stale: bool,
+    block_invalid_submit: bool,
+    comment_prefix: Option<String>,
+    completer: Option<Box<dyn Completer + Send + Sync>>,
+    completion_menu: Option<CompletionMenu>,
 }
 
 impl Repl<32> {
@@ -43,7 +407,12 @@ impl Repl<32> {
         Self::new_with_history(History::new())
     }
 
-    pub fn new_run_fullscreen(executor: impl CommandExecutor) -> io::Result<()> {
+    /// Create a repl whose text starts with `initial`, e.g. a greeting or a first prompt
+    pub fn with_text(initial: impl Into<String>) -> Self {
+        Self::with_text_and_history(initial, History::new())
+    }
+
+    pub fn new_run_fullscreen(executor: impl CommandExecutor) -> Result<(), ReplError> {
         let mut me = Self::new();
         me.run_fullscreen(executor)
     }
@@ -52,54 +421,324 @@ impl Repl<32> {
 impl<const HISTORY_SIZE: usize> Repl<HISTORY_SIZE> {
     pub fn new_with_history(history: History<HISTORY_SIZE>) -> Self {
         Self {
-            current_input: Default::default(),
-            cursor_pos: 0,
-            history,
-            text: Default::default(),
+            editor: LineEditor::new_with_history(history),
+            ..Default::default()
         }
     }
 
-    pub fn run_fullscreen(&mut self, executor: impl CommandExecutor) -> io::Result<()> {
-        crossterm::terminal::enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        crossterm::execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-        let backend = CrosstermBackend::new(stdout);
-        let mut terminal = Terminal::new(backend)?;
+    /// Like [Repl::with_text] with an initial history
+    pub fn with_text_and_history(
+        initial: impl Into<String>,
+        history: History<HISTORY_SIZE>,
+    ) -> Self {
+        Self {
+            text: initial.into(),
+            ..Self::new_with_history(history)
+        }
+    }
+
+    pub fn run_fullscreen(&mut self, mut executor: impl CommandExecutor) -> Result<(), ReplError> {
+        self.run_fullscreen_with(&mut executor)
+    }
+
+    /// Like [Self::run_fullscreen] but borrows the executor, so it can be used after the loop
+    ///
+    /// The terminal is restored even if running the repl failed, the first error is returned.
+    pub fn run_fullscreen_with(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+    ) -> Result<(), ReplError> {
+        crossterm::terminal::enable_raw_mode().map_err(ReplError::RawMode)?;
+        let result = self.run_in_alternate_screen(executor);
 
-        self.run_on_terminal(&mut terminal, executor)?;
+        // restore terminal, every step is taken even if an earlier one failed
+        let mut stdout = io::stdout();
+        let restored = [
+            match self.cursor_style {
+                Some(_) => {
+                    io::Write::write_all(&mut stdout, RESET_CURSOR_SHAPE).map_err(ReplError::Io)
+                }
+                None => Ok(()),
+            },
+            crossterm::terminal::disable_raw_mode().map_err(ReplError::RawMode),
+            crossterm::execute!(stdout, LeaveAlternateScreen, DisableMouseCapture)
+                .map_err(ReplError::AlternateScreen),
+            crossterm::execute!(stdout, Show).map_err(ReplError::Draw),
+        ];
 
-        // restore terminal
-        crossterm::terminal::disable_raw_mode()?;
-        crossterm::execute!(
-            terminal.backend_mut(),
-            LeaveAlternateScreen,
-            DisableMouseCapture
-        )?;
-        terminal.show_cursor()?;
+        restored.into_iter().fold(result, Result::and)
+    }
 
-        Ok(())
+    fn run_in_alternate_screen(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+    ) -> Result<(), ReplError> {
+        let mut stdout = io::stdout();
+        crossterm::execute!(stdout, EnterAlternateScreen, EnableMouseCapture)
+            .map_err(ReplError::AlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        self.run_on_terminal_with(&mut terminal, executor)
     }
 
     pub fn run_on_terminal<B: Backend>(
         &mut self,
         term: &mut Terminal<B>,
         mut executor: impl CommandExecutor,
-    ) -> io::Result<()> {
+    ) -> Result<(), ReplError> {
+        self.run_on_terminal_with(term, &mut executor)
+    }
+
+    /// Like [Self::run_on_terminal] but borrows the executor, so it can be used after the loop
+    pub fn run_on_terminal_with<B: Backend>(
+        &mut self,
+        term: &mut Terminal<B>,
+        executor: &mut impl CommandExecutor,
+    ) -> Result<(), ReplError> {
+        self.run_with_events(term, executor, &mut CrosstermEvents, &mut io::stdout())
+    }
+
+    /// Run the repl on the terminal until an exit key is pressed, reading events from `events`
+    ///
+    /// Terminal effects which can't be drawn, like the bell, are written to `effects_out`,
+    /// see [Self::flush_terminal_effects].
+    pub fn run_with_events<B: Backend>(
+        &mut self,
+        term: &mut Terminal<B>,
+        executor: &mut impl CommandExecutor,
+        events: &mut impl EventSource,
+        effects_out: &mut impl io::Write,
+    ) -> Result<(), ReplError> {
+        self.request_redraw();
+        self.cursor_style_changed |= self.cursor_style.is_some();
         loop {
-            term.draw(|f| {
-                let size = f.size();
-                let (cursor_x, cursor_y) = self.cursor_pos_in(size);
-                f.set_cursor(cursor_x, cursor_y);
-                f.render_widget(&mut *self, size);
-            })?;
-
-            if let Event::Key(key) = event::read()? {
-                match self.feed_key_event(&mut executor, key)? {
-                    ControlFlow::Break(_) => return Ok(()),
-                    _ => (),
+            let flow = self.step(term, executor, events, TICK_RATE)?;
+            self.flush_terminal_effects(effects_out)?;
+            if let ControlFlow::Break(_) = flow {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Write pending terminal effects which can't be drawn, like the bell, to `out`
+    ///
+    /// This has to be called by applications running their own event loop.
+    pub fn flush_terminal_effects(&mut self, out: &mut impl io::Write) -> io::Result<()> {
+        if self.take_bell() {
+            out.write_all(b"\x07")?;
+        }
+
+        if mem::take(&mut self.cursor_style_changed) {
+            match self.cursor_style {
+                Some(CursorStyle { shape, blinking }) => {
+                    // The DECSCUSR code of the steady shape, the blinking one comes before it
+                    let steady = match shape {
+                        CursorShape::Block => 2,
+                        CursorShape::UnderScore => 4,
+                        CursorShape::Line => 6,
+                    };
+                    write!(out, "\x1b[{} q", steady - blinking as u8)?;
                 }
+                None => out.write_all(RESET_CURSOR_SHAPE)?,
+            }
+        }
+
+        out.flush()
+    }
+
+    /// Get the style of the cursor, `None` if the terminal default is used
+    pub fn cursor_style(&self) -> Option<CursorStyle> {
+        self.cursor_style
+    }
+
+    /// Set the style of the cursor, it is applied the next time terminal effects are flushed
+    ///
+    /// This can be used to distinguish modes, `None` restores the default of the terminal.
+    pub fn set_cursor_style(&mut self, style: Option<CursorStyle>) {
+        self.cursor_style_changed |= self.cursor_style != style;
+        self.cursor_style = style;
+    }
+
+    /// Ring the bell the next time terminal effects are flushed
+    pub fn bell(&mut self) {
+        self.bell = true;
+    }
+
+    /// Whether the bell was rung since the terminal effects were last flushed
+    pub fn bell_pending(&self) -> bool {
+        self.bell
+    }
+
+    /// Reset the bell, returning whether it was rung
+    pub fn take_bell(&mut self) -> bool {
+        mem::take(&mut self.bell)
+    }
+
+    /// Run a single iteration of the event loop
+    ///
+    /// This receives streamed output, draws the repl if a redraw was requested and then waits up
+    /// to `timeout` for an event, which is fed to the repl. Handling an event always requests a
+    /// redraw.
+    pub fn step<B: Backend>(
+        &mut self,
+        term: &mut Terminal<B>,
+        executor: &mut impl CommandExecutor,
+        events: &mut impl EventSource,
+        timeout: Duration,
+    ) -> Result<ControlFlow<()>, ReplError> {
+        self.receive_output();
+        // Animate the busy indicator
+        if self.is_busy() && self.is_focused() {
+            self.request_redraw();
+        }
+        if self.redraw.take() {
+            self.draw(term).map_err(ReplError::Draw)?;
+        }
+
+        if !events.poll(timeout).map_err(ReplError::Events)? {
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        self.request_redraw();
+        let event = events.read().map_err(ReplError::Events)?;
+        self.feed_event(executor, event)
+            .map_err(ReplError::Executor)
+    }
+
+    /// Draw the repl and its cursor on the whole terminal
+    pub fn draw<B: Backend>(&mut self, term: &mut Terminal<B>) -> io::Result<()> {
+        term.draw(|f| {
+            let size = f.size();
+            let (cursor_x, cursor_y) = self.cursor_pos_in_absolute(size);
+            f.set_cursor(cursor_x, cursor_y);
+            f.render_widget(&mut *self, size);
+        })?;
+        Ok(())
+    }
+
+    /// Request the repl to be redrawn in the next iteration of the event loop
+    pub fn request_redraw(&self) {
+        self.redraw.request();
+    }
+
+    /// Get a handle which can request redraws from other threads
+    pub fn redraw_handle(&self) -> RedrawHandle {
+        self.redraw.clone()
+    }
+
+    /// Whether a command is running, a spinner is shown instead of the input then
+    pub fn is_busy(&self) -> bool {
+        self.executing.load(Ordering::Acquire) || self.busy.load(Ordering::Acquire)
+    }
+
+    /// Set whether a command is running, e.g. while an asynchronous command is not done
+    ///
+    /// The repl is also busy while the executor runs, a flag set by the executor stays set after
+    /// it returned. [Self::step] redraws the repl every tick while it is busy to animate the
+    /// spinner.
+    pub fn set_busy(&self, busy: bool) {
+        self.busy.store(busy, Ordering::Release);
+        self.request_redraw();
+    }
+
+    /// Mark the repl as busy while the executor runs
+    fn set_executing(&self, executing: bool) {
+        self.executing.store(executing, Ordering::Release);
+        self.request_redraw();
+    }
+
+    /// Get a handle which can mark the repl as busy from other threads, see [Self::set_busy]
+    pub fn busy_handle(&self) -> BusyHandle {
+        BusyHandle {
+            busy: self.busy.clone(),
+            executing: self.executing.clone(),
+            redraw: self.redraw.clone(),
+        }
+    }
+
+    /// Set the frames of the spinner shown while the repl is busy, one is shown per redraw
+    ///
+    /// Defaults to a spinning line made of `|`, `/`, `-` and `\\`.
+    pub fn set_busy_frames<S: Into<String>>(&mut self, frames: impl IntoIterator<Item = S>) {
+        self.busy_frames = frames.into_iter().map(Into::into).collect();
+    }
+
+    /// Whether the terminal has the focus, animations are paused while it hasn't
+    pub fn is_focused(&self) -> bool {
+        !self.unfocused
+    }
+
+    /// Set whether the terminal has the focus, e.g. when the application tracks focus changes
+    ///
+    /// While unfocused the busy spinner stops at its current frame and [Self::step] doesn't
+    /// redraw every tick to animate it.
+    pub fn set_focused(&mut self, focused: bool) {
+        if focused && self.unfocused {
+            self.request_redraw();
+        }
+        self.unfocused = !focused;
+    }
+
+    /// Get a handle which can stream output to the repl, also from other threads
+    ///
+    /// Output sent through the handle is added to the text by the event loop as it arrives,
+    /// so an executor can hand it to a running process instead of buffering all its output.
+    pub fn output_handle(&mut self) -> OutputHandle {
+        let (sender, _) = self.output_channel.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel();
+            (sender, Mutex::new(receiver))
+        });
+        OutputHandle {
+            sender: sender.clone(),
+            redraw: self.redraw.clone(),
+        }
+    }
+
+    /// Add the output sent through [output handles](Self::output_handle) to the text
+    ///
+    /// This is called by [Self::step], applications running their own event loop have to call
+    /// it. Returns whether any output was received.
+    pub fn receive_output(&mut self) -> bool {
+        let received = match &mut self.output_channel {
+            Some((_, receiver)) => receiver
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .try_iter()
+                .collect::<Vec<_>>(),
+            None => return false,
+        };
+        for output in &received {
+            self.print(output);
+        }
+        !received.is_empty()
+    }
+
+    /// Feed a terminal event to the repl, only key events are handled
+    pub fn feed_event(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+        event: Event,
+    ) -> io::Result<ControlFlow<()>> {
+        match event {
+            Event::Key(key) => self.feed_key_event(executor, key),
+            Event::Mouse(_) | Event::Resize(..) => Ok(ControlFlow::Continue(())),
+        }
+    }
+
+    /// Feed terminal events to the repl in order, e.g. to replay a recorded session
+    ///
+    /// Stops at the first event which exits the repl, the remaining events are not fed.
+    pub fn feed_events(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+        events: impl IntoIterator<Item = Event>,
+    ) -> io::Result<ControlFlow<()>> {
+        for event in events {
+            if self.feed_event(executor, event)?.is_break() {
+                return Ok(ControlFlow::Break(()));
             }
         }
+
+        Ok(ControlFlow::Continue(()))
     }
 
     pub fn feed_key_event(
@@ -107,186 +746,4914 @@ impl<const HISTORY_SIZE: usize> Repl<HISTORY_SIZE> {
         executor: &mut impl CommandExecutor,
         key: KeyEvent,
     ) -> io::Result<ControlFlow<()>> {
+        let prev_input = (self.on_input_change.is_some() || self.validator.is_some())
+            .then(|| self.editor.input().to_vec());
+
+        let flow = self.handle_key_event(executor, key)?;
+
+        if self.validation_stale {
+            self.validate();
+        }
+        if let Some(prev_input) = prev_input {
+            if prev_input != self.editor.input() {
+                self.validate();
+                if let Some(on_input_change) = self.on_input_change.as_mut() {
+                    on_input_change(self.editor.input());
+                }
+            }
+        }
+
+        Ok(flow)
+    }
+
+    fn handle_key_event(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+        key: KeyEvent,
+    ) -> io::Result<ControlFlow<()>> {
+        if self.more_pending() && !Self::is_exit_key(key) {
+            self.handle_more_key(key);
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        if self.completion_menu.is_some() && self.handle_menu_key(key) {
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        // Any key other than an exit key cancels a pending exit
+        let exit_pending = mem::take(&mut self.exit_pending);
         match key {
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::CONTROL,
+            } if self.has_input() && self.ctrl_d_policy != CtrlDPolicy::Exit => {
+                match self.ctrl_d_policy {
+                    CtrlDPolicy::DeleteOrExit => self.edit(Action::DeleteForward),
+                    _ => self.bell(),
+                }
+            }
             KeyEvent {
                 code: KeyCode::Char('d' | 'q' | 'x'),
                 modifiers: KeyModifiers::CONTROL,
-            } => return Ok(ControlFlow::Break(())),
+            } => match self.exit_policy {
+                ExitPolicy::RefuseWhenDirty if self.is_dirty() => self.bell(),
+                ExitPolicy::ConfirmWhenDirty if self.is_dirty() && !exit_pending => {
+                    self.exit_pending = true;
+                    self.bell();
+                }
+                _ => return Ok(ControlFlow::Break(())),
+            },
+            KeyEvent {
+                code: KeyCode::PageUp,
+                modifiers: KeyModifiers::NONE,
+            } => self.scroll_up(self.page_height.saturating_sub(1).max(1) as usize),
+            KeyEvent {
+                code: KeyCode::PageDown,
+                modifiers: KeyModifiers::NONE,
+            } => self.scroll_down(self.page_height.saturating_sub(1).max(1) as usize),
+            KeyEvent {
+                code: KeyCode::Up,
+                modifiers: KeyModifiers::CONTROL,
+            } => self.jump_to_prev_prompt(),
+            KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::CONTROL,
+            } => self.jump_to_next_prompt(),
             KeyEvent {
                 code: KeyCode::Char('c'),
                 modifiers: KeyModifiers::CONTROL,
-            } => {
-                self.text.extend(self.current_input.drain(..));
+            } if !self.read_only => {
+                self.scroll_to_bottom();
+                let input = self.editor.take_input();
+                self.echo_input(&input);
                 self.text.push_str("^C");
-                self.cursor_pos = 0;
-                executor.execute(String::new(), &mut self.text)?;
+                self.interrupted = true;
+                self.execute_on_text(executor, String::new())?;
+                self.emit_prompt();
+                self.trim_scrollback();
             }
+            // Chars with an uppercase form of multiple chars don't map to a single action
             KeyEvent {
-                code: code @ (KeyCode::Up | KeyCode::Down),
-                modifiers: KeyModifiers::NONE,
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::SHIFT,
             } => {
-                self.current_input = (if code == KeyCode::Up {
-                    self.history.prev()
-                } else {
-                    self.history.next()
-                }).unwrap_or(&[]).iter().copied().collect();
+                for c in c.to_uppercase() {
+                    self.edit(Action::Insert(c));
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::CONTROL,
+            } if self.repeat_key && !self.read_only => self.repeat_last_command(executor)?,
+            KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::CONTROL,
+            } => self.toggle_last_fold(),
+            KeyEvent {
+                code: KeyCode::Tab,
+                modifiers: KeyModifiers::NONE,
+            } if self.completer.is_some() && !self.read_only => self.complete(),
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers,
+            } if self.is_submit_modifier(modifiers) && !self.read_only => self.submit(executor)?,
+            key => {
+                if let Some(action) = self.key_to_action(key) {
+                    self.edit(action);
+                }
             }
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Get the editing action a key is bound to
+    ///
+    /// Keys which aren't editing actions, like submitting, scrolling or exiting, map to `None`.
+    /// This lets applications showing the repl next to other widgets decide where a key goes.
+    pub fn key_to_action(&self, key: KeyEvent) -> Option<Action> {
+        let action = match key {
+            KeyEvent {
+                code: KeyCode::Up,
+                modifiers: KeyModifiers::NONE,
+            } => Action::HistoryPrev,
+            KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            } => Action::HistoryNext,
+            KeyEvent {
+                code: KeyCode::Up,
+                modifiers: KeyModifiers::ALT,
+            } => Action::MoveLineUp,
+            KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::ALT,
+            } => Action::MoveLineDown,
             KeyEvent {
                 code: KeyCode::Right,
                 modifiers: KeyModifiers::NONE,
-            } => self.set_cursor_pos(self.cursor_pos.saturating_sub(1)),
+            } => Action::MoveRight,
+            KeyEvent {
+                code: KeyCode::Right,
+                modifiers: KeyModifiers::CONTROL,
+            } => Action::MoveWordRight,
+            KeyEvent {
+                code: KeyCode::Left,
+                modifiers: KeyModifiers::CONTROL,
+            } => Action::MoveWordLeft,
+            // Many terminals send Ctrl+H for Ctrl+Backspace
+            KeyEvent {
+                code: KeyCode::Backspace,
+                modifiers: KeyModifiers::CONTROL,
+            }
+            | KeyEvent {
+                code: KeyCode::Char('h' | 'w'),
+                modifiers: KeyModifiers::CONTROL,
+            } => Action::DeleteWordBackward,
+            KeyEvent {
+                code: KeyCode::Delete,
+                modifiers: KeyModifiers::CONTROL,
+            } => Action::DeleteWordForward,
             KeyEvent {
                 code: KeyCode::Left,
                 modifiers: KeyModifiers::NONE,
-            } => self.set_cursor_pos(self.cursor_pos.saturating_add(1)),
+            } => Action::MoveLeft,
             KeyEvent {
                 code: KeyCode::Home,
                 modifiers: _,
-            } => {
-                self.set_cursor_pos(self.current_input.len() as u16);
-            }
+            } => Action::MoveHome,
             KeyEvent {
                 code: KeyCode::End,
                 modifiers: _,
-            } => {
-                self.set_cursor_pos(0);
-            }
+            } => Action::MoveEnd,
+            // Ctrl+G aborts the line in readline
+            KeyEvent {
+                code: KeyCode::Char('g'),
+                modifiers: KeyModifiers::CONTROL,
+            } => Action::Clear,
+            // Ctrl+] is delivered as 0x1D, which crossterm reports as Ctrl+5
+            KeyEvent {
+                code: KeyCode::Char(']' | '5'),
+                modifiers: KeyModifiers::CONTROL,
+            } => Action::MoveMatchingBracket,
+            // Ctrl+_ is the readline undo key, unix terminals send it as 0x1F which crossterm
+            // reports as Ctrl+7. Ctrl+Z works as a fallback where neither is delivered.
+            KeyEvent {
+                code: KeyCode::Char('_' | '7' | 'z'),
+                modifiers: KeyModifiers::CONTROL,
+            } => Action::Undo,
+            KeyEvent {
+                code: KeyCode::Char('_'),
+                modifiers,
+            } if modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT => Action::Undo,
             KeyEvent {
                 code: KeyCode::Char(c),
                 modifiers: KeyModifiers::NONE,
-            } => self
-                .current_input
-                .insert(self.current_input().len() - self.cursor_pos as usize, c),
+            } => Action::Insert(c),
             KeyEvent {
                 code: KeyCode::Char(c),
                 modifiers: KeyModifiers::SHIFT,
             } => {
-                for c in c.to_uppercase() {
-                    self.current_input.insert(self.cursor_pos as usize, c)
+                let mut upper = c.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    (Some(c), None) => Action::Insert(c),
+                    _ => return None,
                 }
             }
             KeyEvent {
                 code: KeyCode::Backspace,
                 modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
-            } => {
-                self.set_cursor_pos(self.cursor_pos);
-                let rm_idx = self.current_input.len() - self.cursor_pos as usize;
-                if rm_idx != 0 {
-                    self.current_input.remove(rm_idx - 1);
-                }
-            }
+            } => Action::DeleteBackward,
             KeyEvent {
                 code: KeyCode::Delete,
                 modifiers: KeyModifiers::NONE,
-            } => {
-                self.set_cursor_pos(self.cursor_pos);
-                if self.cursor_pos != 0 {
-                    self.current_input.remove(self.current_input.len() - self.cursor_pos as usize);
-                    self.cursor_pos = self.cursor_pos.saturating_sub(1);
-                }
+            } => Action::DeleteForward,
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::CONTROL,
+            } if self.has_input() && self.ctrl_d_policy == CtrlDPolicy::DeleteOrExit => {
+                Action::DeleteForward
             }
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers,
+            } if self.is_submit_modifier(modifiers) => return None,
             KeyEvent {
                 code: KeyCode::Enter,
                 modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
-            } => self.submit(executor)?,
-            _ => (),
+            } => Action::Insert('\n'),
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers,
+            } if self.is_newline_modifier(modifiers) => Action::Insert('\n'),
+            _ => return None,
+        };
+
+        Some(action)
+    }
+
+    fn is_submit_modifier(&self, modifiers: KeyModifiers) -> bool {
+        match self.submit_key {
+            SubmitKey::Enter => modifiers == KeyModifiers::NONE || modifiers == KeyModifiers::SHIFT,
+            SubmitKey::CtrlEnter => modifiers == KeyModifiers::CONTROL,
+            SubmitKey::AltEnter => modifiers == KeyModifiers::ALT,
         }
+    }
 
-        Ok(ControlFlow::Continue(()))
+    /// Get which Enter key submits the input
+    pub fn submit_key(&self) -> SubmitKey {
+        self.submit_key
+    }
+
+    /// Set which Enter key submits the input, the other Enter keys insert a line break
+    ///
+    /// The submit key takes precedence over the [newline key](Self::set_newline_key).
+    pub fn set_submit_key(&mut self, submit_key: SubmitKey) {
+        self.submit_key = submit_key;
+    }
+
+    fn is_newline_modifier(&self, modifiers: KeyModifiers) -> bool {
+        match self.newline_key {
+            NewlineKey::Both => {
+                modifiers == KeyModifiers::CONTROL || modifiers == KeyModifiers::ALT
+            }
+            NewlineKey::CtrlEnter => modifiers == KeyModifiers::CONTROL,
+            NewlineKey::AltEnter => modifiers == KeyModifiers::ALT,
+            NewlineKey::Disabled => false,
+        }
+    }
+
+    /// Get which modified Enter key inserts a line break into the input
+    pub fn newline_key(&self) -> NewlineKey {
+        self.newline_key
+    }
+
+    /// Set which modified Enter key inserts a line break into the input
+    pub fn set_newline_key(&mut self, newline_key: NewlineKey) {
+        self.newline_key = newline_key;
+    }
+
+    fn is_exit_key(key: KeyEvent) -> bool {
+        key.modifiers == KeyModifiers::CONTROL && matches!(key.code, KeyCode::Char('d' | 'q' | 'x'))
+    }
+
+    /// Space reveals the next page of held output, Enter the next line and q drops the rest
+    fn handle_more_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(' ') => self.reveal_output(self.page_height.saturating_sub(1) as usize),
+            KeyCode::Enter => self.reveal_output(1),
+            KeyCode::Char('q') => {
+                self.held_output.clear();
+                self.reveal_output(0);
+            }
+            _ => (),
+        }
+    }
+
+    /// Move up to `lines` lines of held output to the text, emitting the prompt once all of it
+    /// is revealed
+    fn reveal_output(&mut self, lines: usize) {
+        let end = match lines.checked_sub(1) {
+            Some(nth) => self
+                .held_output
+                .match_indices('\n')
+                .nth(nth)
+                .map_or(self.held_output.len(), |(idx, _)| idx + 1),
+            None => 0,
+        };
+        self.text.extend(self.held_output.drain(..end));
+        if self.held_output.is_empty() {
+            self.emit_prompt();
+        }
+        self.trim_scrollback();
+    }
+
+    /// Whether output longer than the repl is held back until a key is pressed
+    pub fn paging(&self) -> bool {
+        self.paging
+    }
+
+    /// Set whether output longer than the repl is held back until a key is pressed
+    ///
+    /// Output of a submitted command is then revealed one screen at a time with a "-- more --"
+    /// line below it, like in a pager. Space shows the next page, Enter the next line and q
+    /// drops the rest of the output. The page size is the height the repl was last rendered at.
+    ///
+    /// The executor writes to the text as usual, only the output it appended is held. Output of
+    /// an executor which rewrote the text, e.g. to clear it, is shown at once.
+    pub fn set_paging(&mut self, paging: bool) {
+        self.paging = paging;
+    }
+
+    /// Whether output is held back until a key is pressed, see [Self::set_paging]
+    pub fn more_pending(&self) -> bool {
+        !self.held_output.is_empty()
+    }
+
+    /// Set the completer asked for candidates when Tab is pressed
+    pub fn set_completer(&mut self, completer: impl Completer + Send + Sync + 'static) {
+        self.completer = Some(Box::new(completer));
+    }
+
+    /// Remove the completer set with [Self::set_completer], closing an open completion menu
+    pub fn remove_completer(&mut self) {
+        self.completer = None;
+        self.completion_menu = None;
+    }
+
+    /// Get the open completion menu
+    pub fn completion_menu(&self) -> Option<&CompletionMenu> {
+        self.completion_menu.as_ref()
+    }
+
+    /// Complete the word before the cursor
+    ///
+    /// A single candidate is inserted right away, multiple candidates open a menu below the
+    /// cursor. Up and Down select a candidate, Tab or Enter insert it and Esc closes the menu.
+    /// Any other key closes the menu and is handled as usual.
+    pub fn complete(&mut self) {
+        let Some(completer) = &self.completer else {
+            return;
+        };
+        let cursor = self.editor.cursor_char_index();
+        let candidates = completer.complete(self.editor.input(), cursor);
+        let start = complete::word_start(self.editor.input(), cursor);
+        match CompletionMenu::new(candidates, start) {
+            Some(menu) if menu.candidates().len() == 1 => {
+                self.completion_menu = Some(menu);
+                self.accept_completion();
+            }
+            Some(menu) => self.completion_menu = Some(menu),
+            None => self.bell(),
+        }
+    }
+
+    /// Replace the word before the cursor with the selected candidate and close the menu
+    fn accept_completion(&mut self) {
+        if let Some(menu) = self.completion_menu.take() {
+            let cursor = self.editor.cursor_char_index().max(menu.start());
+            self.editor
+                .replace_range(menu.start()..cursor, menu.selected_candidate());
+        }
+    }
+
+    /// Handle a key while the completion menu is open, returns whether the key was consumed
+    fn handle_menu_key(&mut self, key: KeyEvent) -> bool {
+        let Some(menu) = self.completion_menu.as_mut() else {
+            return false;
+        };
+        if key.modifiers != KeyModifiers::NONE {
+            self.completion_menu = None;
+            return false;
+        }
+        match key.code {
+            KeyCode::Up => menu.select_prev(),
+            KeyCode::Down => menu.select_next(),
+            KeyCode::Tab | KeyCode::Enter => self.accept_completion(),
+            KeyCode::Esc => self.completion_menu = None,
+            _ => {
+                self.completion_menu = None;
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Apply an action to the editor, ringing the bell if it ran into a boundary
+    ///
+    /// Edits scroll back to the bottom and are ignored in [read only](Self::set_read_only) mode.
+    fn edit(&mut self, action: Action) {
+        let action = match action {
+            Action::Insert('\r') if self.newline_policy == NewlinePolicy::Normalize => {
+                Action::Insert('\n')
+            }
+            // Control chars of unbound Ctrl+key combos would end up as raw bytes in the command
+            Action::Insert(c)
+                if c.is_control()
+                    && !matches!(c, '\n' | '\r' | '\t')
+                    && !self.allow_control_chars =>
+            {
+                return;
+            }
+            action => action,
+        };
+        if action.is_edit() {
+            if self.read_only {
+                return;
+            }
+            self.scroll_to_bottom();
+        }
+
+        if !self.editor.apply(action) {
+            self.bell();
+        }
+    }
+
+    /// Append every submitted command to the file at `path`, so that it survives a crash
+    ///
+    /// Entries are written like [History::save] writes them, the file can be read back with
+    /// [History::load_from_path]. A new file starts with the header of the format, a file
+    /// without one is converted to it on the first write. Writing errors don't fail the submit,
+    /// see [Self::last_history_error]. The commands are retried with the next write, as many as
+    /// the history holds.
+    pub fn set_history_file(&mut self, path: impl Into<PathBuf>) {
+        self.history_file = Some(HistoryFile {
+            path: path.into(),
+            pending: Vec::new(),
+            error: None,
+        });
+    }
+
+    /// Get the error of the last failed write to the history file after a submit
+    ///
+    /// Writing after a submit doesn't fail the submit, this tells that the history file is not
+    /// written, e.g. because the disk is full. A later successful write resets it.
+    pub fn last_history_error(&self) -> Option<&io::Error> {
+        self.history_file.as_ref()?.error.as_ref()
+    }
+
+    /// Stop appending submitted commands to the history file, commands not written yet are lost
+    pub fn remove_history_file(&mut self) {
+        self.history_file = None;
+    }
+
+    /// Get after how many submitted commands they are appended to the history file
+    pub fn history_flush_interval(&self) -> usize {
+        self.history_flush_interval.max(1)
+    }
+
+    /// Set after how many submitted commands they are appended to the history file
+    ///
+    /// Defaults to 1, which writes every command right after it was submitted. Larger intervals
+    /// write less often but lose more commands on a crash, [Self::flush_history_file] writes the
+    /// remaining commands e.g. before exiting.
+    pub fn set_history_flush_interval(&mut self, interval: usize) {
+        self.history_flush_interval = interval;
+    }
+
+    /// Append the submitted commands not written yet to the history file
+    pub fn flush_history_file(&mut self) -> io::Result<()> {
+        let Some(file) = self.history_file.as_mut() else {
+            return Ok(());
+        };
+        if file.pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut first = String::new();
+        match File::open(&file.path) {
+            Ok(f) => {
+                BufReader::new(f).read_line(&mut first)?;
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+        let versioned = history::check_header(&first)?;
+        let mut out = Vec::new();
+        if !versioned {
+            history::write_header(&mut out)?;
+        }
+        // A file written before line breaks were escaped is converted, otherwise escaped entries
+        // appended to it would be read back with their backslashes
+        let legacy = !first.is_empty() && !versioned;
+        if legacy {
+            for command in fs::read_to_string(&file.path)?.lines() {
+                history::write_entry(&mut out, &command.chars().collect::<Vec<_>>())?;
+            }
+        }
+        for entry in &file.pending {
+            history::write_entry(&mut out, entry)?;
+        }
+
+        if legacy {
+            // The converted file replaces the old one at once, a failed write keeps the old one
+            let mut tmp = file.path.clone().into_os_string();
+            tmp.push(".tmp");
+            fs::write(&tmp, &out)?;
+            fs::rename(&tmp, &file.path)?;
+        } else {
+            let mut f = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&file.path)?;
+            io::Write::write_all(&mut f, &out)?;
+        }
+        file.pending.clear();
+        Ok(())
+    }
+
+    /// Whether the last line was interrupted with Ctrl+C instead of being submitted
+    ///
+    /// Reset when the next command is submitted.
+    pub fn was_interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// Whether the input can't be edited
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Set whether the input can't be edited, e.g. to use the repl as a live log
+    ///
+    /// Typing, deleting, submitting and pasting are ignored while the cursor can still be
+    /// moved, the text scrolled and the repl exited.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// The number of lines the view is scrolled up from the bottom
+    pub fn scroll_offset(&self) -> usize {
+        self.scroll_offset
+    }
+
+    /// Whether the view is scrolled to the bottom, where new output and the input are shown
+    pub fn is_at_bottom(&self) -> bool {
+        self.scroll_offset == 0
+    }
+
+    /// Scroll the view up by `lines`, at most until the first line is at the top
+    ///
+    /// PageUp scrolls up by a page. The height of a page is the height the repl was last
+    /// rendered at.
+    pub fn scroll_up(&mut self, lines: usize) {
+        let max_offset = self.total_rows().saturating_sub(self.page_height as usize);
+        self.scroll_offset = self.scroll_offset.saturating_add(lines).min(max_offset);
+        self.damage_text();
+        self.request_redraw();
+    }
+
+    /// Get the number of rows of the text, prompt and input
+    fn total_rows(&self) -> usize {
+        self.shown_text().split('\n').count()
+            + self.active_prompt.matches('\n').count()
+            + self.current_input().iter().filter(|&&c| c == '\n').count()
+    }
+
+    /// Get the rows of the shown text the prompts of the submitted commands are on
+    fn prompt_rows(&self) -> Vec<usize> {
+        let mut rows = Vec::with_capacity(self.blocks.len());
+        let mut counted = 0;
+        let mut row = 0;
+        // Rows hidden by folds before the current block, they are within the counted rows
+        let mut hidden = 0;
+        for (idx, block) in self.blocks.iter().enumerate() {
+            let (Some(before), Some(block_text)) = (
+                self.text.get(counted..block.start),
+                self.text.get(self.block_range(idx)),
+            ) else {
+                break;
+            };
+            row += before.matches('\n').count();
+            counted = block.start;
+            rows.push(row - hidden);
+            if let (true, Some(first_break), Some(last_break)) =
+                (block.folded, block_text.find('\n'), block_text.rfind('\n'))
+            {
+                hidden += block_text[first_break..last_break].matches('\n').count();
+            }
+        }
+
+        rows
+    }
+
+    /// Scroll the view so that `row` is at its top, as far as possible
+    fn scroll_row_to_top(&mut self, row: usize) {
+        let offset = self
+            .total_rows()
+            .saturating_sub(self.page_height as usize)
+            .saturating_sub(row);
+        match offset.cmp(&self.scroll_offset) {
+            std::cmp::Ordering::Greater => self.scroll_up(offset - self.scroll_offset),
+            std::cmp::Ordering::Less => self.scroll_down(self.scroll_offset - offset),
+            std::cmp::Ordering::Equal => (),
+        }
+    }
+
+    /// Get the row at the top of the view
+    fn top_row(&self) -> usize {
+        self.total_rows()
+            .saturating_sub(self.scroll_offset)
+            .saturating_sub(self.page_height as usize)
+    }
+
+    /// Scroll up to the prompt of the previous command above the view, bound to Ctrl+Up
+    pub fn jump_to_prev_prompt(&mut self) {
+        let top = self.top_row();
+        match self.prompt_rows().into_iter().rev().find(|&row| row < top) {
+            Some(row) => self.scroll_row_to_top(row),
+            None => self.bell(),
+        }
+    }
+
+    /// Scroll down to the prompt of the next command below the top of the view, bound to
+    /// Ctrl+Down
+    ///
+    /// After the last command the view is scrolled to the bottom.
+    pub fn jump_to_next_prompt(&mut self) {
+        let top = self.top_row();
+        match self.prompt_rows().into_iter().find(|&row| row > top) {
+            Some(row) => self.scroll_row_to_top(row),
+            None if !self.is_at_bottom() => self.scroll_to_bottom(),
+            None => self.bell(),
+        }
+    }
+
+    /// Scroll the view down by `lines`, PageDown scrolls down by a page
+    pub fn scroll_down(&mut self, lines: usize) {
+        if lines != 0 {
+            self.damage_text();
+        }
+        self.scroll_offset = self.scroll_offset.saturating_sub(lines);
+        self.request_redraw();
+    }
+
+    /// Scroll the view to the bottom, where the input is
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll_down(self.scroll_offset);
+    }
+
+    /// Get the line editor holding the input, cursor and history
+    pub fn editor(&self) -> &LineEditor<HISTORY_SIZE> {
+        &self.editor
+    }
+
+    /// Get mutable access to the line editor
+    ///
+    /// The input may be changed through it, so the [validation error](Self::validation_error) is
+    /// cleared until the input is validated again with the next key event.
+    pub fn editor_mut(&mut self) -> &mut LineEditor<HISTORY_SIZE> {
+        self.invalidate_validation();
+        &mut self.editor
     }
 
     pub fn history(&self) -> &History<HISTORY_SIZE> {
-        &self.history
+        self.editor.history()
     }
 
     pub fn history_mut(&mut self) -> &mut History<HISTORY_SIZE> {
-        &mut self.history
+        self.editor.history_mut()
+    }
+
+    /// Consume the repl, returning its history, e.g. to save it on exit
+    pub fn take_history(self) -> History<HISTORY_SIZE> {
+        self.editor.into_history()
+    }
+
+    /// Consume the repl, returning its history and text
+    pub fn into_parts(self) -> (History<HISTORY_SIZE>, String) {
+        (self.editor.into_history(), self.text)
     }
 
     pub fn current_input(&self) -> &[char] {
-        &self.current_input
+        self.editor.input()
     }
 
+    /// Get mutable access to the current input
+    ///
+    /// The [validation error](Self::validation_error) is cleared until the input is validated
+    /// again with the next key event.
     pub fn current_input_mut(&mut self) -> &mut Vec<char> {
-        &mut self.current_input
+        self.invalidate_validation();
+        self.editor.input_mut()
+    }
+
+    /// Insert text at the cursor as a single edit, placing the cursor after it
+    ///
+    /// Unlike [Self::feed_paste] nothing is submitted, line breaks are inserted as they are.
+    pub fn insert_str_at_cursor(&mut self, s: &str) {
+        self.editor.insert_str(s);
+        self.validate();
+    }
+
+    /// Insert pasted text at the cursor, submitting lines as configured with
+    /// [Self::set_paste_policy]
+    ///
+    /// Applications can call this with the text of a bracketed paste.
+    pub fn feed_paste(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+        text: &str,
+    ) -> io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+
+        let text = match self.newline_policy {
+            NewlinePolicy::Normalize => util::normalize_newlines(text),
+            NewlinePolicy::Keep => Cow::Borrowed(text),
+        };
+        let text = text.as_ref();
+        match self.paste_policy {
+            PastePolicy::SubmitCompleteLines => {
+                let mut lines = text.split('\n');
+                let partial = lines.next_back().unwrap_or_default();
+                for line in lines {
+                    self.editor.insert_str(line);
+                    self.submit(executor)?;
+                }
+                self.editor.insert_str(partial);
+            }
+            PastePolicy::SubmitOnFinalNewline => match text.strip_suffix('\n') {
+                Some(lines) => {
+                    for line in lines.split('\n') {
+                        self.editor.insert_str(line);
+                        self.submit(executor)?;
+                    }
+                }
+                None => self.editor.insert_str(text),
+            },
+            PastePolicy::NeverSubmit => self.editor.insert_str(text),
+        }
+        self.validate();
+
+        Ok(())
+    }
+
+    /// Submit every line read from `reader` as if it was typed, e.g. to prime the repl from a pipe
+    pub fn prime_from_reader(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+        reader: impl BufRead,
+    ) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            self.set_input(line.strip_suffix('\r').unwrap_or(&line));
+            self.submit(executor)?;
+        }
+
+        Ok(())
+    }
+
+    /// Submit every line piped into stdin, does nothing if stdin is a terminal
+    ///
+    /// This has to be called before the terminal is set up for the repl, e.g. before
+    /// [Self::run_fullscreen], so that `echo cmd | app` runs `cmd` before going interactive.
+    pub fn prime_from_stdin(&mut self, executor: &mut impl CommandExecutor) -> io::Result<()> {
+        let stdin = io::stdin();
+        if stdin.is_terminal() {
+            return Ok(());
+        }
+
+        self.prime_from_reader(executor, stdin.lock())
+    }
+
+    /// Get how line breaks in pasted text are handled
+    pub fn paste_policy(&self) -> PastePolicy {
+        self.paste_policy
+    }
+
+    /// Set how line breaks in pasted text are handled
+    pub fn set_paste_policy(&mut self, policy: PastePolicy) {
+        self.paste_policy = policy;
+    }
+
+    /// Get how carriage returns in pasted and typed input are handled
+    pub fn newline_policy(&self) -> NewlinePolicy {
+        self.newline_policy
+    }
+
+    /// Set how carriage returns in pasted and typed input are handled
+    pub fn set_newline_policy(&mut self, policy: NewlinePolicy) {
+        self.newline_policy = policy;
+    }
+
+    /// Whether typed control chars other than line breaks and tabs are inserted into the input
+    pub fn allow_control_chars(&self) -> bool {
+        self.allow_control_chars
+    }
+
+    /// Set whether typed control chars other than line breaks and tabs are inserted, by default
+    /// they are dropped
+    ///
+    /// Allowed control chars are shown as their control picture with
+    /// [Self::set_show_whitespace]. Pasted text is not filtered.
+    pub fn set_allow_control_chars(&mut self, allow: bool) {
+        self.allow_control_chars = allow;
+    }
+
+    /// Get what happens when an empty input is submitted
+    pub fn empty_submit_policy(&self) -> EmptySubmitPolicy {
+        self.empty_submit_policy
+    }
+
+    /// Set what happens when an empty input is submitted
+    pub fn set_empty_submit_policy(&mut self, policy: EmptySubmitPolicy) {
+        self.empty_submit_policy = policy;
+    }
+
+    /// Set a callback which is invoked with the new input every time
+    /// [feed_key_event](Self::feed_key_event) changed the current input
+    pub fn set_on_input_change(&mut self, callback: impl FnMut(&[char]) + Send + Sync + 'static) {
+        self.on_input_change = Some(Box::new(callback));
+    }
+
+    /// Remove the callback set with [Self::set_on_input_change]
+    pub fn remove_on_input_change(&mut self) {
+        self.on_input_change = None;
+    }
+
+    /// Set a validator checking the input every time it changes
+    ///
+    /// The error message of invalid input is shown dimmed after it. Invalid input can still be
+    /// submitted unless [Self::set_block_invalid_submit] is set.
+    pub fn set_validator(&mut self, validator: impl Validator + Send + Sync + 'static) {
+        self.validator = Some(Box::new(validator));
+        self.validate();
+    }
+
+    /// Remove the validator set with [Self::set_validator]
+    pub fn remove_validator(&mut self) {
+        self.validator = None;
+        self.validation_error = None;
+    }
+
+    /// Get the error message of the validator for the current input
+    pub fn validation_error(&self) -> Option<&str> {
+        self.validation_error.as_deref()
+    }
+
+    /// Whether invalid input can't be submitted
+    pub fn block_invalid_submit(&self) -> bool {
+        self.block_invalid_submit
+    }
+
+    /// Set whether invalid input can't be submitted, submitting it rings the bell instead
+    pub fn set_block_invalid_submit(&mut self, block: bool) {
+        self.block_invalid_submit = block;
+    }
+
+    /// Clear the validation error of an input which may be changed outside of the repl's control
+    fn invalidate_validation(&mut self) {
+        if self.validator.is_some() {
+            self.validation_error = None;
+            self.validation_stale = true;
+        }
+    }
+
+    /// Run the validator on the current input, returns whether it is valid
+    fn validate(&mut self) -> bool {
+        self.validation_stale = false;
+        self.validation_error = self
+            .validator
+            .as_ref()
+            .and_then(|validator| validator.validate(self.editor.input()).err());
+        self.validation_error.is_none()
+    }
+
+    /// Whether the current input is not empty
+    pub fn has_input(&self) -> bool {
+        self.editor.has_input()
+    }
+
+    /// Whether there is input which would be lost on exit
+    ///
+    /// Unlike [Self::has_input] this is false if the input is an unmodified history entry.
+    pub fn is_dirty(&self) -> bool {
+        self.editor.is_dirty()
+    }
+
+    /// Get what happens when an exit key is pressed
+    pub fn exit_policy(&self) -> ExitPolicy {
+        self.exit_policy
+    }
+
+    /// Set what happens when an exit key is pressed
+    pub fn set_exit_policy(&mut self, policy: ExitPolicy) {
+        self.exit_policy = policy;
+    }
+
+    /// Get what Ctrl+D does
+    pub fn ctrl_d_policy(&self) -> CtrlDPolicy {
+        self.ctrl_d_policy
+    }
+
+    /// Set what Ctrl+D does, it exits like the other exit keys by default
+    pub fn set_ctrl_d_policy(&mut self, policy: CtrlDPolicy) {
+        self.ctrl_d_policy = policy;
+    }
+
+    /// Whether the next exit key press exits even though the input is dirty
+    pub fn exit_pending(&self) -> bool {
+        self.exit_pending
+    }
+
+    /// Replace the current input with the given text and place the cursor at its end
+    pub fn set_input(&mut self, text: &str) {
+        self.editor.set_input(text);
+        self.validate();
     }
 
+    /// Get the cursor position relative to the origin of the area the repl is rendered in
     pub fn cursor_pos_in(&self, rect: Rect) -> (u16, u16) {
-        let mut lines = self.text.lines().rev().peekable();
-        let last_line_len = lines.peek().map(|s| s.len()).unwrap_or(0);
-        let max_height = rect.height.saturating_sub(rect.top());
-        if self.text.ends_with('\n') {
-            (
-                self.current_input
-                    .len()
-                    .saturating_sub(self.cursor_pos as usize) as u16,
-                (lines.count() as u16).clamp(0, max_height),
-            )
+        let height = rect.height as usize;
+        let (input, cursor_idx) = if self.echo() {
+            (self.editor.input(), self.editor.cursor_char_index())
         } else {
-            (
-                (last_line_len + self.current_input.len()).saturating_sub(self.cursor_pos as usize)
-                    as u16,
-                (lines.count().saturating_sub(1) as u16).clamp(0, max_height),
-            )
+            (&[][..], 0)
+        };
+        let before_cursor = &input[..cursor_idx];
+
+        let x = match before_cursor.iter().rposition(|&c| c == '\n') {
+            Some(line_break) => cursor_idx - line_break - 1,
+            None => {
+                // The prompt is drawn in front of the input, continuing the last line of the text
+                let (prompt_start, prompt_line) = match self.active_prompt.rsplit_once('\n') {
+                    Some((_, prompt_line)) => (0, prompt_line),
+                    None if self.splits_input() || self.prompt_on_new_line() => {
+                        (0, &*self.active_prompt)
+                    }
+                    None => {
+                        let last_line = self.text.rsplit('\n').next().unwrap_or_default();
+                        let last_line = last_line.trim_end_matches('\r');
+                        (last_line.chars().count(), self.active_prompt.as_str())
+                    }
+                };
+                prompt_start + prompt_line.chars().count() + cursor_idx
+            }
+        } + self.gutter_width() as usize;
+        // Keep the cursor inside of the area, even if it has no width or height
+        let x = x.min((rect.width as usize).saturating_sub(1));
+
+        // The same rows as in render
+        let prompt_breaks = self.active_prompt.matches('\n').count();
+        let breaks_before_cursor = before_cursor.iter().filter(|&&c| c == '\n').count();
+        if self.splits_input() {
+            let mut y = prompt_breaks + breaks_before_cursor;
+            if self.input_position == InputPosition::Bottom {
+                let input_rows = prompt_breaks + input.iter().filter(|&&c| c == '\n').count() + 1;
+                let fixed_rows = input_rows.min(height) + usize::from(self.separator.is_some());
+                let mut text_rows = self.shown_text().split('\n').count();
+                if self.text.is_empty() || self.text.ends_with('\n') {
+                    text_rows -= 1;
+                }
+                let text_rows = text_rows
+                    .saturating_sub(self.scroll_offset)
+                    .min(height.saturating_sub(fixed_rows));
+                y += text_rows + usize::from(self.separator.is_some());
+            }
+            return (x as u16, y.min(height.saturating_sub(1)) as u16);
         }
+
+        let text_rows = (self.shown_text().split('\n').count()
+            + usize::from(self.prompt_on_new_line())
+            + prompt_breaks)
+            .min(height)
+            .max(1);
+        let input_breaks = input.iter().filter(|&&c| c == '\n').count();
+        let skipped_rows = (text_rows + input_breaks).saturating_sub(height);
+        let y = (text_rows - 1 + breaks_before_cursor)
+            .saturating_sub(skipped_rows)
+            .saturating_add(self.scroll_offset)
+            .min(height.saturating_sub(1));
+
+        (x as u16, y as u16)
     }
 
-    pub fn set_cursor_pos(&mut self, pos: u16) {
-        self.cursor_pos = pos.clamp(0, self.current_input.len() as u16)
+    /// Get how many rows the prompt and the input take in an area `width` columns wide
+    ///
+    /// Lines longer than the width count as wrapped onto further rows. The repl clips long lines
+    /// itself, this is meant for sizing the region of the input in a layout that wraps. The
+    /// prompt is counted from its last line break, so it is assumed to start a row.
+    pub fn input_height(&self, width: u16) -> u16 {
+        let width = width.saturating_sub(self.gutter_width()).max(1) as usize;
+        let input = if self.echo() {
+            self.editor.input()
+        } else {
+            &[][..]
+        };
+        let mut rows = 0;
+        let mut line_len = 0usize;
+        for c in self.active_prompt.chars().chain(input.iter().copied()) {
+            if c == '\n' {
+                rows += line_len.max(1).div_ceil(width);
+                line_len = 0;
+            } else {
+                line_len += 1;
+            }
+        }
+        rows += line_len.max(1).div_ceil(width);
+
+        u16::try_from(rows).unwrap_or(u16::MAX)
     }
 
-    pub fn text(&self) -> &str {
-        self.text.as_ref()
+    /// Get the cursor position on the terminal when the repl is rendered in the given area
+    pub fn cursor_pos_in_absolute(&self, rect: Rect) -> (u16, u16) {
+        let (x, y) = self.cursor_pos_in(rect);
+        (rect.x.saturating_add(x), rect.y.saturating_add(y))
     }
 
-    pub fn text_mut(&mut self) -> &mut String {
-        &mut self.text
+    /// Get where the cursor is placed in an entry recalled from the history
+    pub fn history_cursor(&self) -> HistoryCursor {
+        self.editor.history_cursor()
     }
 
-    pub fn submit(&mut self, executor: &mut impl CommandExecutor) -> io::Result<()> {
-        self.set_cursor_pos(0);
-        self.history.push(self.current_input.iter().copied().collect());
-        self.text.extend(self.current_input.iter());
-        executor.execute(self.current_input.drain(..).collect(), &mut self.text)
+    /// Set where the cursor is placed in an entry recalled from the history
+    pub fn set_history_cursor(&mut self, history_cursor: HistoryCursor) {
+        self.editor.set_history_cursor(history_cursor);
     }
-}
 
-impl<const HISTORY_SIZE: usize> Debug for Repl<HISTORY_SIZE> {
-    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
-        fmt.debug_struct("Repl")
-            .field("current_input", &self.current_input)
-            .field("cursor_pos", &self.cursor_pos)
-            .field("history", &self.history)
-            .field("text", &self.text)
-            .finish()
+    /// Get where Home and End move the cursor
+    pub fn home_end(&self) -> HomeEnd {
+        self.editor.home_end()
     }
-}
 
-pub trait CommandExecutor {
-    fn execute<'a>(&mut self, command: String, repl_buffer: &mut String) -> io::Result<()>;
-}
+    /// Set where Home and End move the cursor, to the start and end of the input by default
+    pub fn set_home_end(&mut self, home_end: HomeEnd) {
+        self.editor.set_home_end(home_end);
+    }
 
-impl CommandExecutor for () {
-    fn execute<'a>(&mut self, _command: String, _repl_buffer: &mut String) -> io::Result<()> {
-        Ok(())
+    /// Whether Left and Right move over line breaks to the previous and next line
+    pub fn line_edge_wrap(&self) -> bool {
+        self.editor.line_edge_wrap()
     }
-}
 
-impl<F: FnMut(String, &mut String) -> io::Result<()>> CommandExecutor for F {
-    fn execute<'a>(&mut self, command: String, repl_buffer: &mut String) -> io::Result<()> {
-        self(command, repl_buffer)
+    /// Set whether Left and Right move over line breaks, on by default
+    ///
+    /// When off the cursor stops at the start and end of every line of a multiline input.
+    pub fn set_line_edge_wrap(&mut self, wrap: bool) {
+        self.editor.set_line_edge_wrap(wrap);
     }
-}
 
-impl<const HISTORY_SIZE: usize> Widget for &mut Repl<HISTORY_SIZE> {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        let max_height = area.height.saturating_sub(area.top());
+    /// Get how a line break inserted into the input is indented
+    pub fn auto_indent(&self) -> AutoIndent {
+        self.editor.auto_indent()
+    }
+
+    /// Set how a line break inserted into the input is indented, it is not indented by default
+    pub fn set_auto_indent(&mut self, auto_indent: AutoIndent) {
+        self.editor.set_auto_indent(auto_indent);
+    }
+
+    /// Whether moving past either end of the history continues at the other end
+    pub fn history_wrap(&self) -> bool {
+        self.editor.history_wrap()
+    }
+
+    /// Set whether moving past either end of the history continues at the other end, off by default
+    pub fn set_history_wrap(&mut self, history_wrap: bool) {
+        self.editor.set_history_wrap(history_wrap);
+    }
+
+    /// Get what separates words for word wise movement and deletion
+    pub fn word_class(&self) -> WordClass {
+        self.editor.word_class()
+    }
+
+    /// Set what separates words for word wise movement and deletion
+    pub fn set_word_class(&mut self, word_class: WordClass) {
+        self.editor.set_word_class(word_class);
+    }
+
+    pub fn set_cursor_pos(&mut self, pos: u16) {
+        self.editor.set_cursor_pos(pos);
+    }
+
+    /// The char index in the input where the next typed char is inserted
+    ///
+    /// This is `current_input().len()` minus the cursor position, which counts the chars after
+    /// the cursor. The index is at most the length of the input.
+    pub fn cursor_char_index(&self) -> usize {
+        self.editor.cursor_char_index()
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_ref()
+    }
+
+    /// Get mutable access to the text
+    ///
+    /// The scroll offset is kept, so it may point past the start of a shortened text until the
+    /// next scroll. The scrollback limit is applied with the next output and changes are not
+    /// written to the [output sink](Self::set_output_sink). Prefer [Self::set_text] or
+    /// [Self::print] where possible.
+    pub fn text_mut(&mut self) -> &mut String {
+        self.damage_text();
+        // The text may be changed anywhere, so where the commands are can't be tracked
+        self.blocks.clear();
+        &mut self.text
+    }
+
+    /// Replace the whole text, scrolling back to the bottom
+    ///
+    /// The scrollback limit is applied to the new text. The output sink is not written to, it
+    /// only receives output.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.blocks.clear();
+        self.scroll_offset = 0;
+        self.trim_scrollback();
+        self.request_redraw();
+    }
+
+    /// Get the number of submitted commands whose output can be folded
+    ///
+    /// Commands are forgotten when the text is replaced or changed with [Self::text_mut], and
+    /// when they are removed by the scrollback limit.
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Whether the output of the command at `idx`, counted from the oldest, is folded
+    pub fn is_folded(&self, idx: usize) -> bool {
+        self.blocks.get(idx).is_some_and(|block| block.folded)
+    }
+
+    /// Fold or unfold the output of the command at `idx`, counted from the oldest
+    ///
+    /// A folded command shows only its line, with the number of hidden lines after it. The last
+    /// line of its output stays visible, as the next prompt may continue it. This only affects
+    /// rendering, the text is not changed.
+    pub fn set_folded(&mut self, idx: usize, folded: bool) {
+        if let Some(block) = self.blocks.get_mut(idx) {
+            block.folded = folded;
+            self.damage_text();
+            self.request_redraw();
+        }
+    }
+
+    /// Fold or unfold the output of all commands
+    pub fn set_all_folded(&mut self, folded: bool) {
+        for idx in 0..self.blocks.len() {
+            self.set_folded(idx, folded);
+        }
+    }
+
+    /// Fold or unfold the output of the last command, bound to Ctrl+O
+    pub fn toggle_last_fold(&mut self) {
+        match self.blocks.len().checked_sub(1) {
+            Some(last) => self.set_folded(last, !self.is_folded(last)),
+            None => self.bell(),
+        }
+    }
+
+    /// Get the command at `idx`, counted from the oldest, with its output
+    pub fn command_block(&self, idx: usize) -> Option<CommandBlock<'_>> {
+        let block = self.blocks.get(idx)?;
+        let range = self.block_range(idx);
+        Some(CommandBlock {
+            prompt: self.text.get(block.start..block.command)?,
+            command: self.text.get(block.command..block.output)?,
+            output: self.text.get(block.output..range.end)?,
+            folded: block.folded,
+        })
+    }
+
+    /// Iterate over the submitted commands still in the text with their output, oldest first
+    ///
+    /// The flat text stays the source of truth, the blocks are views into it. Text written
+    /// before the first command, e.g. a greeting, is in no block.
+    pub fn command_blocks(&self) -> impl Iterator<Item = CommandBlock<'_>> {
+        (0..self.blocks.len()).filter_map(|idx| self.command_block(idx))
+    }
+
+    /// Get the byte range of the block at `idx` in the text, lasting until the next block
+    fn block_range(&self, idx: usize) -> Range<usize> {
+        let end = self
+            .blocks
+            .get(idx + 1)
+            .map_or(self.text.len(), |next| next.start);
+        self.blocks[idx].start..end
+    }
+
+    /// Get the text as it is shown, with the output of folded commands hidden
+    ///
+    /// The folded text is built once per change of the text, it is needed several times per
+    /// frame.
+    fn shown_text(&self) -> ShownText<'_> {
+        if !self.blocks.iter().any(|block| block.folded) {
+            return ShownText::Text(&self.text);
+        }
+
+        let mut cache = self
+            .shown_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match &*cache {
+            Some((generation, shown)) if *generation == self.text_generation => {
+                ShownText::Folded(shown.clone())
+            }
+            _ => {
+                let shown = Arc::<str>::from(self.fold_text());
+                *cache = Some((self.text_generation, shown.clone()));
+                ShownText::Folded(shown)
+            }
+        }
+    }
+
+    /// Build the text with the output of folded commands hidden
+    fn fold_text(&self) -> String {
+        let mut shown = String::with_capacity(self.text.len());
+        let mut copied = 0;
+        for (idx, block) in self.blocks.iter().enumerate() {
+            let range = self.block_range(idx);
+            let (Some(block_text), true) = (self.text.get(range.clone()), range.start >= copied)
+            else {
+                continue;
+            };
+            let (Some(first_break), Some(last_break)) =
+                (block_text.find('\n'), block_text.rfind('\n'))
+            else {
+                continue;
+            };
+            if !block.folded || first_break == last_break {
+                continue;
+            }
+            let hidden = block_text[first_break + 1..last_break]
+                .matches('\n')
+                .count()
+                + 1;
+            shown.push_str(&self.text[copied..range.start + first_break]);
+            shown.push_str(&format!(" [+{} lines]", hidden));
+            copied = range.start + last_break;
+        }
+        shown.push_str(&self.text[copied..]);
+
+        shown
+    }
+
+    /// Capture the text, prompt, input and history of the session
+    pub fn snapshot(&self) -> ReplSnapshot {
+        ReplSnapshot {
+            text: self.text.clone(),
+            prompt: self.prompt.clone(),
+            active_prompt: self.active_prompt.clone(),
+            input: self.editor.input().iter().collect(),
+            cursor: self.editor.cursor_char_index(),
+            history: self.editor.history().iter_strings().collect(),
+        }
+    }
+
+    /// Continue the session captured in a snapshot, replacing the text, input and history
+    ///
+    /// A cursor past the end of the input is placed at its end. History entries beyond the
+    /// capacity are dropped oldest first and the undo steps of the old input are forgotten.
+    pub fn restore(&mut self, snapshot: ReplSnapshot) {
+        self.prompt = snapshot.prompt;
+        self.active_prompt = snapshot.active_prompt;
+        self.editor.take_input();
+        self.editor.set_input(&snapshot.input);
+        self.editor.set_cursor_char_index(snapshot.cursor);
+        self.editor
+            .history_mut()
+            .replace_entries(snapshot.history.iter().map(|entry| entry.chars().collect()));
+        self.completion_menu = None;
+        self.validate();
+        self.set_text(snapshot.text);
+    }
+
+    /// Get the command submitted last, as it was typed
+    ///
+    /// Unlike the newest history entry this includes blank commands, which the history skips,
+    /// and works with a history of capacity 0. Comments are not commands and don't change it,
+    /// neither do commands submitted without [echo](Self::set_echo).
+    pub fn last_command(&self) -> Option<&[char]> {
+        self.last_command.as_deref()
+    }
+
+    /// Whether the lines of the text have to be rendered again in the next frame
+    ///
+    /// While the text, the scroll position and the size of the repl don't change, only the
+    /// prompt and the input are rendered again and the text lines of the last frame are reused.
+    /// This keeps typing cheap with a lot of output.
+    pub fn needs_full_redraw(&self) -> bool {
+        !self.is_cached(&self.state)
+    }
+
+    /// Set the height used for page wise scrolling and paging
+    ///
+    /// Rendering `&mut Repl` as a [Widget] sets it to the height of the area, when rendering it as
+    /// a [StatefulWidget] it has to be set manually.
+    pub fn set_page_height(&mut self, page_height: u16) {
+        self.page_height = page_height;
+    }
+
+    fn damage_text(&mut self) {
+        self.text_generation = self.text_generation.wrapping_add(1);
+    }
+
+    fn is_cached(&self, state: &ReplState) -> bool {
+        state
+            .text_cache
+            .as_ref()
+            .is_some_and(|cache| cache.generation == self.text_generation)
+    }
+
+    /// Build the visible lines of the text, the prompt and input are added by the caller
+    fn text_lines(&self, area: Rect) -> Vec<Spans<'static>> {
+        // Scrolled up rows are cut off at the bottom after the input was added
+        let shown = self.shown_text();
+        let visible = util::last_lines(&shown, area.height as usize + self.scroll_offset);
+        let rows = visible
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect::<Vec<_>>();
+        let last_row = rows.len() - 1;
+        rows.into_iter()
+            .enumerate()
+            .map(|(row, line)| match self.overflow {
+                Overflow::Truncate(marker) if row != last_row => {
+                    Spans::from(util::truncate_line(line, area.width as usize, marker).into_owned())
+                }
+                _ => Spans::from(line.to_owned()),
+            })
+            .collect()
+    }
+
+    /// Draw the candidates below the cursor, or above it if there is more room there
+    fn render_completion_menu(&self, menu: &CompletionMenu, area: Rect, buf: &mut Buffer) {
+        let (cursor_x, cursor_y) = self.cursor_pos_in(area);
+        let word_len = self.editor.cursor_char_index().saturating_sub(menu.start());
+        let x = (cursor_x as usize).saturating_sub(word_len);
+        let below = (area.height as usize).saturating_sub(cursor_y as usize + 1);
+        let above = cursor_y as usize;
+        let candidates = menu.candidates();
+        let rows = candidates.len().min(below.max(above));
+        if rows == 0 {
+            return;
+        }
+        let first_row = if rows <= below {
+            cursor_y as usize + 1
+        } else {
+            cursor_y as usize - rows
+        };
+        let first = menu.selected().saturating_sub(rows - 1);
+        let width = candidates
+            .iter()
+            .map(|candidate| candidate.chars().count())
+            .max()
+            .unwrap_or_default()
+            + 1;
+        let width = width.min((area.width as usize).saturating_sub(x));
+
+        for (row, idx) in (first..first + rows).enumerate() {
+            let style = if idx == menu.selected() {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            // Candidates start below the word they replace
+            let line = format!("{:<1$}", candidates[idx], width);
+            buf.set_stringn(
+                area.x + x as u16,
+                area.y + (first_row + row) as u16,
+                line,
+                width,
+                style,
+            );
+        }
+    }
+
+    /// Append the prompt and the input to the lines, continuing the last line
+    fn push_input<'a>(&'a self, lines: &mut Vec<Spans<'a>>, state: &mut ReplState) {
+        let mut input = Cow::Borrowed(self.editor.input());
+        let mut highlights = self
+            .highlighter
+            .as_ref()
+            .map(|h| h.highlight(&input))
+            .unwrap_or_default();
+        highlights.extend(self.bracket_highlights());
+        if self.show_whitespace {
+            let dim = Style::default().add_modifier(Modifier::DIM);
+            let (shown, whitespace) = highlight::show_whitespace(&input, dim);
+            input = Cow::Owned(shown);
+            highlights.extend(whitespace);
+        }
+        util::push_multiline_span(lines, Span::raw(self.active_prompt.clone()));
+        if self.more_pending() {
+            let reversed = Style::default().add_modifier(Modifier::REVERSED);
+            util::push_multiline_span(lines, Span::styled("-- more --", reversed));
+        } else if self.is_busy() {
+            let frame = match self.busy_frames.len() {
+                0 => DEFAULT_BUSY_FRAMES[state.busy_frame % DEFAULT_BUSY_FRAMES.len()],
+                len => &self.busy_frames[state.busy_frame % len],
+            };
+            if self.is_focused() {
+                state.busy_frame = state.busy_frame.wrapping_add(1);
+            }
+            util::push_multiline_span(lines, Span::raw(frame.to_owned()));
+        } else if self.echo() {
+            for span in highlight::styled_spans(&input, self.input_style, &highlights) {
+                util::push_multiline_span(lines, span);
+            }
+            if let Some(error) = &self.validation_error {
+                let dim = Style::default().add_modifier(Modifier::DIM);
+                util::push_multiline_span(lines, Span::styled(format!("  {}", error), dim));
+            }
+        }
+    }
+
+    /// Iterate over all lines of the text, not only the visible ones, e.g. to export a session
+    ///
+    /// Line breaks are stripped like in [str::lines].
+    pub fn output_lines(&self) -> impl Iterator<Item = &str> {
+        self.text.lines()
+    }
+
+    /// Append to the text without going through an executor and request a redraw
+    ///
+    /// The repl is not thread safe, output produced on other threads has to be sent to the
+    /// thread running the event loop, [Self::redraw_handle] can notify it.
+    pub fn print(&mut self, s: &str) {
+        // print can't fail, the sink is only a copy of the text
+        let _ = Self::tee(&mut self.output_sink, s);
+        self.text.push_str(s);
+        self.trim_scrollback();
+        self.request_redraw();
+    }
+
+    /// Append to the text followed by a line break, see [Self::print]
+    pub fn println(&mut self, s: &str) {
+        self.print(s);
+        self.print("\n");
+    }
+
+    /// Set a writer receiving a copy of all output, e.g. to log it to a file
+    ///
+    /// Output of the executor and of [Self::print] is written to the sink as it is added to the
+    /// text, before the scrollback is trimmed. Echoed input and prompts are not written. Errors
+    /// writing executor output are returned from the submitting method, errors in
+    /// [Self::print] are ignored. The sink is never flushed by the repl, so a buffered writer
+    /// has to be flushed by the application.
+    pub fn set_output_sink(&mut self, sink: impl io::Write + Send + Sync + 'static) {
+        self.output_sink = Some(Box::new(sink));
+    }
+
+    /// Remove the writer set with [Self::set_output_sink]
+    pub fn remove_output_sink(&mut self) {
+        self.output_sink = None;
+    }
+
+    /// The text an executor appended after `start`
+    ///
+    /// Executors may also remove text, `start` is then past the end or inside of a char and
+    /// nothing counts as appended.
+    fn appended(text: &str, start: usize) -> &str {
+        text.get(start..).unwrap_or_default()
+    }
+
+    fn tee(sink: &mut Option<Box<dyn io::Write + Send + Sync>>, output: &str) -> io::Result<()> {
+        match sink {
+            Some(sink) => sink.write_all(output.as_bytes()),
+            None => Ok(()),
+        }
+    }
+
+    /// The maximum number of lines kept in the text, `None` if unlimited
+    pub fn scrollback_limit(&self) -> Option<usize> {
+        self.scrollback_limit
+    }
+
+    /// Set the maximum number of lines kept in the text, the oldest lines are removed first
+    pub fn set_scrollback_limit(&mut self, limit: Option<usize>) {
+        self.scrollback_limit = limit;
+        self.trim_scrollback();
+    }
+
+    fn trim_scrollback(&mut self) {
+        self.damage_text();
+        if let Some(limit) = self.scrollback_limit {
+            let len = self.text.len();
+            util::trim_lines(&mut self.text, limit);
+            let cut = len - self.text.len();
+            if cut > 0 {
+                // Commands which were cut off are forgotten, their remaining output is kept
+                self.blocks.retain(|block| block.start >= cut);
+                for block in &mut self.blocks {
+                    block.start -= cut;
+                    block.command -= cut;
+                    block.output -= cut;
+                }
+            }
+        }
+    }
+
+    /// Get the style the current input is rendered with
+    pub fn input_style(&self) -> Style {
+        self.input_style
+    }
+
+    /// Set the style the current input is rendered with, the text is not affected by this
+    pub fn set_input_style(&mut self, style: Style) {
+        self.input_style = style;
+    }
+
+    /// Set the highlighter used to style parts of the current input on top of the input style
+    pub fn set_highlighter(&mut self, highlighter: impl Highlighter + Send + Sync + 'static) {
+        self.highlighter = Some(Box::new(highlighter));
+    }
+
+    /// Get how the bracket at the cursor and the one matching it are highlighted
+    pub fn bracket_highlight(&self) -> Option<BracketHighlight> {
+        self.bracket_highlight
+    }
+
+    /// Set how the bracket at the cursor, or else right before it, and its match are
+    /// highlighted, `None` to turn it off
+    pub fn set_bracket_highlight(&mut self, highlight: Option<BracketHighlight>) {
+        self.bracket_highlight = highlight;
+    }
+
+    fn bracket_highlights(&self) -> Vec<(Range<usize>, Style)> {
+        let Some(highlight) = self.bracket_highlight else {
+            return Vec::new();
+        };
+        let input = self.editor.input();
+        let cursor = self.editor.cursor_char_index();
+        if let Some((bracket, matched)) = bracket::at_cursor(input, cursor) {
+            return vec![
+                (bracket..bracket + 1, highlight.matched),
+                (matched..matched + 1, highlight.matched),
+            ];
+        }
+        let unmatched = [Some(cursor), cursor.checked_sub(1)]
+            .into_iter()
+            .flatten()
+            .find(|&idx| input.get(idx).is_some_and(|&c| bracket::is_bracket(c)));
+        match (unmatched, highlight.unmatched) {
+            (Some(idx), Some(style)) => vec![(idx..idx + 1, style)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Remove the highlighter set with [Self::set_highlighter]
+    pub fn remove_highlighter(&mut self) {
+        self.highlighter = None;
+    }
+
+    /// Whether invisible chars in the input are rendered visibly
+    pub fn show_whitespace(&self) -> bool {
+        self.show_whitespace
+    }
+
+    /// Set whether invisible chars in the input are rendered visibly, in a dim style
+    ///
+    /// Trailing spaces are shown as '·', tabs as '→' and other control chars as their control
+    /// picture. This only affects rendering, submitted commands keep the real chars.
+    pub fn set_show_whitespace(&mut self, show_whitespace: bool) {
+        self.show_whitespace = show_whitespace;
+    }
+
+    /// Whether the input is rendered while it is typed
+    pub fn echo(&self) -> bool {
+        !self.no_echo
+    }
+
+    /// Set whether the input is rendered while it is typed, like `stty echo`
+    ///
+    /// Without echo only the prompt is shown and the cursor stays at its end, so not even the
+    /// length of the input leaks, e.g. for tokens. The input can still be edited and submitted
+    /// but it isn't written to the text on submit. Neither is it added to the history, the
+    /// history file or the [last command](Self::last_command).
+    pub fn set_echo(&mut self, echo: bool) {
+        self.no_echo = !echo;
+    }
+
+    /// Whether the lines of the text are numbered in a gutter left of them
+    pub fn show_line_numbers(&self) -> bool {
+        self.show_line_numbers
+    }
+
+    /// Set whether the lines of the text are numbered in a gutter left of them
+    ///
+    /// The line the prompt and input continue is not numbered. The gutter grows with the number of
+    /// lines.
+    pub fn set_show_line_numbers(&mut self, show_line_numbers: bool) {
+        self.show_line_numbers = show_line_numbers;
+        self.damage_text();
+    }
+
+    /// Width of the line number gutter including the space separating it from the text
+    fn gutter_width(&self) -> u16 {
+        if !self.show_line_numbers {
+            return 0;
+        }
+        let numbered_lines = self.shown_text().matches('\n').count().max(1);
+        numbered_lines.to_string().len() as u16 + 1
+    }
+
+    /// Get where the prompt and input are placed relative to the text
+    pub fn input_position(&self) -> InputPosition {
+        self.input_position
+    }
+
+    /// Set where the prompt and input are placed relative to the text
+    ///
+    /// With [InputPosition::Top] the prompt doesn't continue the last line of the text, the text
+    /// is shown below the input instead.
+    pub fn set_input_position(&mut self, input_position: InputPosition) {
+        self.input_position = input_position;
+    }
+
+    /// Get the line drawn between the text and the input
+    pub fn separator(&self) -> Option<Separator> {
+        self.separator
+    }
+
+    /// Set a line drawn between the text and the input, e.g. made of '─'
+    ///
+    /// With a separator the prompt doesn't continue the last line of the text.
+    pub fn set_separator(&mut self, separator: Option<Separator>) {
+        self.separator = separator;
+    }
+
+    /// Whether the input is rendered apart from the text instead of continuing its last line
+    fn splits_input(&self) -> bool {
+        self.input_position == InputPosition::Top || self.separator.is_some()
+    }
+
+    /// Get how lines of the text wider than the repl are displayed
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// Set how lines of the text wider than the repl are displayed
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+        self.damage_text();
+    }
+
+    /// Get the prompt which is shown before every new input line
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    /// Set the prompt which is shown before every new input line
+    ///
+    /// The prompt is emitted after every submitted or interrupted command and written to the
+    /// text together with the input. An empty prompt leaves the placement of prompts
+    /// completely to the executor. The prompt may contain line breaks, e.g. to show a banner line
+    /// above the input, the input starts after its last line.
+    ///
+    /// The prompt already shown in front of the input, see [Self::current_prompt], is kept. The
+    /// new prompt is shown from the next [Self::emit_prompt] on, e.g. after the next submit.
+    pub fn set_prompt(&mut self, prompt: impl Into<String>) {
+        self.prompt = prompt.into();
+    }
+
+    /// Whether a line break is inserted before the prompt if the text does not end with one
+    pub fn ensure_newline(&self) -> bool {
+        self.ensure_newline
+    }
+
+    /// Set whether a line break is inserted before the prompt if the text does not end with one
+    ///
+    /// This makes sure every input line starts on its own line, even if the executor did not
+    /// terminate its output or the command was interrupted with Ctrl+C.
+    pub fn set_ensure_newline(&mut self, ensure_newline: bool) {
+        self.ensure_newline = ensure_newline;
+    }
+
+    /// Get the marker written before a line break inserted by [Self::set_ensure_newline]
+    pub fn missing_newline_marker(&self) -> Option<&str> {
+        self.missing_newline_marker.as_deref()
+    }
+
+    /// Set a marker written before a line break inserted by [Self::set_ensure_newline], e.g.
+    /// `%` like zsh does, so that it is visible that the output didn't end with one
+    pub fn set_missing_newline_marker(&mut self, marker: impl Into<String>) {
+        self.missing_newline_marker = Some(marker.into());
+    }
+
+    /// Remove the marker set with [Self::set_missing_newline_marker]
+    pub fn remove_missing_newline_marker(&mut self) {
+        self.missing_newline_marker = None;
+    }
+
+    /// Get where the prompt is placed if the text doesn't end with a line break
+    pub fn prompt_line(&self) -> PromptLine {
+        self.prompt_line
+    }
+
+    /// Set where the prompt is placed if the text doesn't end with a line break
+    ///
+    /// Unlike [Self::set_ensure_newline], which only adds a line break when the prompt is
+    /// emitted, [PromptLine::NewLine] also moves the prompt below output printed after that.
+    /// The line break is written to the text when the input is submitted.
+    pub fn set_prompt_line(&mut self, prompt_line: PromptLine) {
+        self.prompt_line = prompt_line;
+        self.damage_text();
+    }
+
+    /// Whether the prompt is shown on a new line instead of continuing the last line of the text
+    fn prompt_on_new_line(&self) -> bool {
+        self.prompt_line == PromptLine::NewLine
+            && !self.text.is_empty()
+            && !self.text.ends_with('\n')
+    }
+
+    /// Set a callback computing the prompt every time it is emitted
+    ///
+    /// This takes precedence over the prompt set with [Self::set_prompt] and allows the prompt
+    /// to change between commands, e.g. to show the working directory.
+    pub fn set_prompt_provider(
+        &mut self,
+        provider: impl FnMut(&Self) -> String + Send + Sync + 'static,
+    ) {
+        self.prompt_provider = Some(Box::new(provider));
+    }
+
+    /// Remove the callback set with [Self::set_prompt_provider]
+    pub fn remove_prompt_provider(&mut self) {
+        self.prompt_provider = None;
+    }
+
+    /// Start a new input line with the prompt, respecting [Self::ensure_newline]
+    ///
+    /// A prompt which is still shown in front of the input is written to the text first.
+    pub fn emit_prompt(&mut self) {
+        self.damage_text();
+        self.text.push_str(&mem::take(&mut self.active_prompt));
+        if self.ensure_newline && !self.text.is_empty() && !self.text.ends_with('\n') {
+            if let Some(marker) = &self.missing_newline_marker {
+                self.text.push_str(marker);
+            }
+            self.text.push('\n');
+        }
+
+        if let Some(mut provider) = self.prompt_provider.take() {
+            self.active_prompt = provider(self);
+            self.prompt_provider = Some(provider);
+        } else {
+            self.active_prompt = self.prompt.clone();
+        }
+    }
+
+    /// Get the prompt shown in front of the current input
+    pub fn current_prompt(&self) -> &str {
+        &self.active_prompt
+    }
+
+    /// Push a submitted command to the history and queue it for the history file
+    fn push_history(&mut self, entry: Vec<char>) {
+        let Some(file) = self.history_file.as_mut() else {
+            self.editor.history_mut().push(entry);
+            return;
+        };
+        file.pending.push(entry.clone());
+        self.editor.history_mut().push(entry);
+        // The history skips blank commands, they are not written either
+        if self.editor.history().newest() != file.pending.last().map(Vec::as_slice) {
+            file.pending.pop();
+        }
+        // While writing fails only the commands the history can hold are kept for the retry
+        if file.pending.len() > HISTORY_SIZE {
+            file.pending.remove(0);
+        }
+        if file.pending.len() >= self.history_flush_interval.max(1) {
+            // A failing write must not end the session, the commands are retried with the next
+            // flush
+            let error = self.flush_history_file().err();
+            if let Some(file) = self.history_file.as_mut() {
+                file.error = error;
+            }
+        }
+    }
+
+    /// Write the prompt and the input to the text, as they were shown
+    ///
+    /// Returns where the echoed prompt and input start in the text.
+    fn echo_input(&mut self, input: &[char]) -> (usize, usize) {
+        self.damage_text();
+        let prompt = mem::take(&mut self.active_prompt);
+        if self.no_command_echo {
+            return (self.text.len(), self.text.len());
+        }
+        if self.prompt_on_new_line() {
+            self.text.push('\n');
+        }
+        let start = self.text.len();
+        self.text.push_str(&prompt);
+        let command = self.text.len();
+        if self.echo() {
+            self.text.extend(input);
+        }
+
+        (start, command)
+    }
+
+    /// Whether submitted commands are written to the text together with their prompt
+    pub fn echo_submitted_command(&self) -> bool {
+        !self.no_command_echo
+    }
+
+    /// Set whether submitted commands are written to the text together with their prompt
+    ///
+    /// Applications that show the prompt and the input apart from the text can turn this off,
+    /// the text then only contains what the executors produced.
+    pub fn set_echo_submitted_command(&mut self, echo: bool) {
+        self.no_command_echo = !echo;
+    }
+
+    /// Add an alias, commands whose first word is `name` get it replaced by `expansion`
+    ///
+    /// Returns the previous expansion of this alias if there was one.
+    pub fn add_alias(
+        &mut self,
+        name: impl Into<String>,
+        expansion: impl Into<String>,
+    ) -> Option<String> {
+        self.aliases.insert(name.into(), expansion.into())
+    }
+
+    /// Remove an alias, returning its expansion if it existed
+    pub fn remove_alias(&mut self, name: &str) -> Option<String> {
+        self.aliases.remove(name)
+    }
+
+    /// Get all aliases mapped to their expansion
+    pub fn aliases(&self) -> &HashMap<String, String> {
+        &self.aliases
+    }
+
+    /// Whether the history stores the expanded command instead of the typed one
+    pub fn store_expanded_aliases(&self) -> bool {
+        self.store_expanded_aliases
+    }
+
+    /// Set whether the history stores the expanded command instead of the typed one
+    pub fn set_store_expanded_aliases(&mut self, store_expanded: bool) {
+        self.store_expanded_aliases = store_expanded;
+    }
+
+    /// The prefix marking a submitted line as a comment, `None` if comments are disabled
+    pub fn comment_prefix(&self) -> Option<&str> {
+        self.comment_prefix.as_deref()
+    }
+
+    /// Set the prefix marking a submitted line as a comment, e.g. `#`
+    ///
+    /// Comments are echoed to the text but neither executed nor added to the history. Leading
+    /// whitespace before the prefix is ignored. An empty prefix disables comments.
+    pub fn set_comment_prefix(&mut self, prefix: impl Into<String>) {
+        self.comment_prefix = Some(prefix.into()).filter(|prefix| !prefix.is_empty());
+    }
+
+    /// Remove the prefix set with [Self::set_comment_prefix], disabling comments
+    pub fn remove_comment_prefix(&mut self) {
+        self.comment_prefix = None;
+    }
+
+    fn is_comment(&self, input: &str) -> bool {
+        self.comment_prefix
+            .as_deref()
+            .is_some_and(|prefix| input.trim_start().starts_with(prefix))
+    }
+
+    /// Expand the alias matching the first word of the command, the remaining arguments are kept
+    ///
+    /// Returns `None` if the first word is not an alias.
+    pub fn expand_alias(&self, command: &str) -> Option<String> {
+        let command = command.trim_start();
+        let name_end = command.find(char::is_whitespace).unwrap_or(command.len());
+        let (name, args) = command.split_at(name_end);
+        self.aliases
+            .get(name)
+            .map(|expansion| format!("{}{}", expansion, args))
+    }
+
+    /// Revert the last edit of the current input, rings the bell if there is nothing to undo
+    ///
+    /// Edits are undone one key press at a time, submitting a command forgets all edits.
+    pub fn undo(&mut self) {
+        self.edit(Action::Undo);
+    }
+
+    pub fn submit(&mut self, executor: &mut impl CommandExecutor) -> io::Result<()> {
+        if self.block_invalid_submit && !self.validate() {
+            self.bell();
+            return Ok(());
+        }
+        if !self.has_input() {
+            match self.empty_submit_policy {
+                EmptySubmitPolicy::Execute => (),
+                EmptySubmitPolicy::Ignore => return Ok(()),
+                EmptySubmitPolicy::EmitNewline => {
+                    self.echo_input(&[]);
+                    self.text.push('\n');
+                    self.emit_prompt();
+                    self.trim_scrollback();
+                    return Ok(());
+                }
+            }
+        }
+
+        let input = self.editor.take_input();
+        self.validate();
+        self.submit_chars(executor, input, true)
+    }
+
+    /// Submit a command as if it was typed, e.g. for startup scripts or menu entries
+    ///
+    /// The command is echoed, added to the history and executed like a typed one. The current
+    /// input is kept and continues after the next prompt.
+    pub fn submit_command(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+        command: impl AsRef<str>,
+    ) -> io::Result<()> {
+        self.submit_chars(executor, command.as_ref().chars().collect(), true)
+    }
+
+    /// Submit the [last command](Self::last_command) again, bound to Ctrl+P if
+    /// [enabled](Self::set_repeat_key)
+    ///
+    /// The current input is kept like with [Self::submit_command]. Rings the bell if no command
+    /// was submitted yet.
+    pub fn repeat_last_command(&mut self, executor: &mut impl CommandExecutor) -> io::Result<()> {
+        match self.last_command.clone() {
+            Some(command) => {
+                let record = !self.skip_repeat_history;
+                self.submit_chars(executor, command, record)
+            }
+            None => {
+                self.bell();
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether repeated commands are left out of the history
+    pub fn skip_repeat_history(&self) -> bool {
+        self.skip_repeat_history
+    }
+
+    /// Set whether commands repeated with [Self::repeat_last_command] are left out of the history
+    pub fn set_skip_repeat_history(&mut self, skip: bool) {
+        self.skip_repeat_history = skip;
+    }
+
+    /// Whether Ctrl+P repeats the last command
+    pub fn repeat_key(&self) -> bool {
+        self.repeat_key
+    }
+
+    /// Set whether Ctrl+P calls [Self::repeat_last_command], off by default
+    ///
+    /// Ctrl+P selects the previous history entry in readline, so users may expect that instead.
+    pub fn set_repeat_key(&mut self, repeat_key: bool) {
+        self.repeat_key = repeat_key;
+    }
+
+    fn submit_chars(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+        input: Vec<char>,
+        record: bool,
+    ) -> io::Result<()> {
+        self.interrupted = false;
+        self.scroll_to_bottom();
+        let (start, command) = self.echo_input(&input);
+        let typed = input.iter().collect::<String>();
+        if self.is_comment(&typed) {
+            // Without the echo there is no line to end
+            if !self.no_command_echo {
+                self.text.push('\n');
+            }
+            self.emit_prompt();
+            self.trim_scrollback();
+            return Ok(());
+        }
+        self.blocks.push(BlockMark {
+            start,
+            command,
+            output: self.text.len(),
+            folded: false,
+        });
+        // Input typed without echo may be a secret, it is not kept for later
+        let record = record && self.echo();
+        if self.echo() {
+            self.last_command = Some(input.clone());
+        }
+        let command = match self.expand_alias(&typed) {
+            Some(expanded) => {
+                if record {
+                    let store_expanded = self.store_expanded_aliases;
+                    self.push_history(if store_expanded {
+                        expanded.chars().collect()
+                    } else {
+                        input
+                    });
+                }
+                expanded
+            }
+            None => {
+                if record {
+                    self.push_history(input);
+                }
+                typed
+            }
+        };
+        self.set_executing(true);
+        let result = self.execute_on_text(executor, command);
+        self.set_executing(false);
+        match result? {
+            Some(start) if self.paging && self.page_height > 1 => {
+                // Output of an earlier command may still be held
+                let output = self.text.split_off(start);
+                self.held_output.push_str(&output);
+                self.reveal_output(self.page_height as usize - 1);
+            }
+            _ => {
+                self.emit_prompt();
+                self.trim_scrollback();
+            }
+        }
+        Ok(())
+    }
+
+    /// Let the executor write to the text and copy what it appended to the output sink
+    ///
+    /// Executors may also shorten or rewrite the text, e.g. to clear it. The commands in the
+    /// text are forgotten then, as where they are can't be tracked. Returns where the appended
+    /// output starts, none if the text was rewritten.
+    fn execute_on_text(
+        &mut self,
+        executor: &mut impl CommandExecutor,
+        command: String,
+    ) -> io::Result<Option<usize>> {
+        let start = self.text.len();
+        // Comparing the last echoed command is enough to notice a cleared text without copying
+        // all of it
+        let last_echo = self.last_echo().map(str::to_owned);
+        let result = executor.execute(command, &mut self.text);
+        let rewritten = self.text.len() < start || self.last_echo() != last_echo.as_deref();
+        if rewritten {
+            self.blocks.clear();
+            self.damage_text();
+        }
+        result?;
+        Self::tee(&mut self.output_sink, Self::appended(&self.text, start))?;
+        Ok((!rewritten && self.text.is_char_boundary(start)).then_some(start))
+    }
+
+    /// Get the prompt and command of the last submitted command as they are in the text
+    fn last_echo(&self) -> Option<&str> {
+        let block = self.blocks.last()?;
+        self.text.get(block.start..block.output)
+    }
+}
+
+impl<const HISTORY_SIZE: usize> Debug for Repl<HISTORY_SIZE> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Repl")
+            .field("editor", &self.editor)
+            .field("text", &self.text)
+            .field("blocks", &self.blocks)
+            .field("shown_cache", &self.shown_cache)
+            .field("prompt", &self.prompt)
+            .field("active_prompt", &self.active_prompt)
+            .field("ensure_newline", &self.ensure_newline)
+            .field("missing_newline_marker", &self.missing_newline_marker)
+            .field("prompt_line", &self.prompt_line)
+            .field("input_style", &self.input_style)
+            .field("aliases", &self.aliases)
+            .field("store_expanded_aliases", &self.store_expanded_aliases)
+            .field("no_command_echo", &self.no_command_echo)
+            .field("on_input_change", &self.on_input_change.is_some())
+            .field("highlighter", &self.highlighter.is_some())
+            .field("show_whitespace", &self.show_whitespace)
+            .field("show_line_numbers", &self.show_line_numbers)
+            .field("no_echo", &self.no_echo)
+            .field("overflow", &self.overflow)
+            .field("paging", &self.paging)
+            .field("held_output", &self.held_output)
+            .field("scroll_offset", &self.scroll_offset)
+            .field("read_only", &self.read_only)
+            .field("newline_key", &self.newline_key)
+            .field("submit_key", &self.submit_key)
+            .field("output_sink", &self.output_sink.is_some())
+            .field("state", &self.state)
+            .field("text_generation", &self.text_generation)
+            .field("output_channel", &self.output_channel.is_some())
+            .field("redraw", &self.redraw)
+            .field("scrollback_limit", &self.scrollback_limit)
+            .field("paste_policy", &self.paste_policy)
+            .field("newline_policy", &self.newline_policy)
+            .field("allow_control_chars", &self.allow_control_chars)
+            .field("empty_submit_policy", &self.empty_submit_policy)
+            .field("prompt_provider", &self.prompt_provider.is_some())
+            .field("bell", &self.bell)
+            .field("exit_policy", &self.exit_policy)
+            .field("ctrl_d_policy", &self.ctrl_d_policy)
+            .field("exit_pending", &self.exit_pending)
+            .field("interrupted", &self.interrupted)
+            .field("last_command", &self.last_command)
+            .field("skip_repeat_history", &self.skip_repeat_history)
+            .field("repeat_key", &self.repeat_key)
+            .field("history_file", &self.history_file)
+            .field("history_flush_interval", &self.history_flush_interval)
+            .field("busy", &self.busy)
+            .field("executing", &self.executing)
+            .field("busy_frames", &self.busy_frames)
+            .field("unfocused", &self.unfocused)
+            .field("input_position", &self.input_position)
+            .field("separator", &self.separator)
+            .field("bracket_highlight", &self.bracket_highlight)
+            .field("validator", &self.validator.is_some())
+            .field("validation_error", &self.validation_error)
+            .field("validation_stale", &self.validation_stale)
+            .field("block_invalid_submit", &self.block_invalid_submit)
+            .field("comment_prefix", &self.comment_prefix)
+            .field("completer", &self.completer.is_some())
+            .field("completion_menu", &self.completion_menu)
+            .field("cursor_style", &self.cursor_style)
+            .finish()
+    }
+}
+
+/// A handle requesting the repl to be redrawn, it can be sent to other threads
+///
+/// This is useful if output is produced in the background, the event loop redraws within
+/// [TICK_RATE] even if no event arrives.
+#[derive(Debug, Default, Clone)]
+pub struct RedrawHandle(Arc<AtomicBool>);
+
+impl RedrawHandle {
+    /// Request a redraw
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Whether a redraw is requested
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::AcqRel)
+    }
+}
+
+/// A handle marking the repl as busy, it can be sent to other threads
+///
+/// Changing the state requests a redraw.
+#[derive(Debug, Clone)]
+pub struct BusyHandle {
+    busy: Arc<AtomicBool>,
+    executing: Arc<AtomicBool>,
+    redraw: RedrawHandle,
+}
+
+impl BusyHandle {
+    /// Set whether a command is running, see [Repl::set_busy]
+    pub fn set(&self, busy: bool) {
+        self.busy.store(busy, Ordering::Release);
+        self.redraw.request();
+    }
+
+    /// Whether a command is running, see [Repl::is_busy]
+    pub fn is_busy(&self) -> bool {
+        self.executing.load(Ordering::Acquire) || self.busy.load(Ordering::Acquire)
+    }
+}
+
+/// A handle streaming output to the repl, it can be sent to other threads
+///
+/// Sending output requests a redraw, so it shows up within [TICK_RATE].
+#[derive(Debug, Clone)]
+pub struct OutputHandle {
+    sender: Sender<String>,
+    redraw: RedrawHandle,
+}
+
+impl OutputHandle {
+    /// Append to the text of the repl, returns false if the repl was dropped
+    pub fn print(&self, s: impl Into<String>) -> bool {
+        let sent = self.sender.send(s.into()).is_ok();
+        self.redraw.request();
+        sent
+    }
+
+    /// Append to the text of the repl followed by a line break, see [Self::print]
+    pub fn println(&self, s: impl Into<String>) -> bool {
+        let mut line = s.into();
+        line.push('\n');
+        self.print(line)
+    }
+}
+
+pub trait CommandExecutor {
+    // The lifetime is part of the public signature, implementations may name it
+    #[allow(clippy::extra_unused_lifetimes)]
+    fn execute<'a>(&mut self, command: String, repl_buffer: &mut String) -> io::Result<()>;
+}
+
+impl CommandExecutor for () {
+    fn execute<'a>(&mut self, _command: String, _repl_buffer: &mut String) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F: FnMut(String, &mut String) -> io::Result<()>> CommandExecutor for F {
+    fn execute<'a>(&mut self, command: String, repl_buffer: &mut String) -> io::Result<()> {
+        self(command, repl_buffer)
+    }
+}
+
+/// An executor returning its output instead of appending it to the text, the repl appends it
+///
+/// This wraps closures like `|command| Ok(format!("{}\n", command))`, which can't touch what
+/// is already in the text.
+#[derive(Debug, Clone, Copy)]
+pub struct ReturnOutput<F>(pub F);
+
+impl<F: FnMut(String) -> io::Result<String>> CommandExecutor for ReturnOutput<F> {
+    fn execute(&mut self, command: String, repl_buffer: &mut String) -> io::Result<()> {
+        let output = (self.0)(command)?;
+        repl_buffer.push_str(&output);
+        Ok(())
+    }
+}
+
+impl<const HISTORY_SIZE: usize> Widget for &mut Repl<HISTORY_SIZE> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        self.page_height = area.height;
+        let mut state = mem::take(&mut self.state);
+        StatefulWidget::render(&*self, area, buf, &mut state);
+        self.state = state;
+    }
+}
+
+impl<const HISTORY_SIZE: usize> StatefulWidget for &Repl<HISTORY_SIZE> {
+    type State = ReplState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut ReplState) {
+        let height = area.height as usize;
+
+        let cache = match state.text_cache.take() {
+            Some(cache) if cache.area == area && self.is_cached(state) => cache,
+            _ => {
+                let gutter = self.gutter_width().min(area.width);
+                let text_area = Rect {
+                    x: area.x + gutter,
+                    width: area.width - gutter,
+                    ..area
+                };
+                let lines = self.text_lines(text_area);
+                TextCache {
+                    area,
+                    generation: self.text_generation,
+                    first_line: self.shown_text().matches('\n').count() + 2 - lines.len(),
+                    lines,
+                    gutter,
+                }
+            }
+        };
+        let mut lines = cache.lines.clone();
+        let (gutter, first_line) = (cache.gutter, cache.first_line);
+        state.text_cache = Some(cache);
+
+        let text_width = area.width - gutter;
+        let (shown, numbers) = if !self.splits_input() {
+            // The prompt and input continue the last line of the text, only complete lines are
+            // numbered
+            if self.prompt_on_new_line() {
+                lines.push(Spans::default());
+            }
+            let text_rows = lines.len();
+            self.push_input(&mut lines, state);
+            lines.truncate(lines.len().saturating_sub(self.scroll_offset).max(1));
+            let skipped_rows = lines.len().saturating_sub(height);
+            let numbers = (skipped_rows..lines.len())
+                .map(|row| (row + 1 < text_rows).then_some(first_line + row))
+                .collect::<Vec<_>>();
+            (lines.split_off(skipped_rows), numbers)
+        } else {
+            let mut input_lines = Vec::new();
+            self.push_input(&mut input_lines, state);
+            input_lines.truncate(height);
+            // The empty line after a trailing line break is where the input would continue
+            if self.text.is_empty() || self.text.ends_with('\n') {
+                lines.pop();
+            }
+            lines.truncate(lines.len().saturating_sub(self.scroll_offset));
+            let separator = self.separator.map(|separator| {
+                let line = separator.symbol.to_string().repeat(text_width as usize);
+                Spans::from(Span::styled(line, separator.style))
+            });
+            let fixed_rows = input_lines.len() + usize::from(separator.is_some());
+            let skipped_rows = lines
+                .len()
+                .saturating_sub(height.saturating_sub(fixed_rows));
+            let text_numbers = (skipped_rows..lines.len()).map(|row| Some(first_line + row));
+            // iter::repeat_n needs Rust 1.82
+            #[allow(clippy::manual_repeat_n)]
+            let fixed_numbers = iter::repeat(None).take(fixed_rows);
+            let text_lines = lines.split_off(skipped_rows);
+
+            match self.input_position {
+                InputPosition::Bottom => (
+                    text_lines
+                        .into_iter()
+                        .chain(separator)
+                        .chain(input_lines)
+                        .collect(),
+                    text_numbers.chain(fixed_numbers).collect::<Vec<_>>(),
+                ),
+                InputPosition::Top => (
+                    input_lines
+                        .into_iter()
+                        .chain(separator)
+                        .chain(text_lines)
+                        .collect(),
+                    fixed_numbers.chain(text_numbers).collect(),
+                ),
+            }
+        };
+        if gutter > 0 {
+            let dim = Style::default().add_modifier(Modifier::DIM);
+            let numbers = numbers
+                .into_iter()
+                .map(|number| match number {
+                    Some(number) => Spans::from(Span::styled(
+                        format!("{:>1$} ", number, gutter as usize - 1),
+                        dim,
+                    )),
+                    None => Spans::default(),
+                })
+                .collect::<Vec<_>>();
+            let gutter_area = Rect {
+                width: gutter,
+                ..area
+            };
+            Paragraph::new(Text::from(numbers)).render(gutter_area, buf);
+        }
+        let text_area = Rect {
+            x: area.x + gutter,
+            width: text_width,
+            ..area
+        };
+        Paragraph::new(Text::from(shown)).render(text_area, buf);
+
+        if let Some(menu) = &self.completion_menu {
+            self.render_completion_menu(menu, area, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tui::{backend::TestBackend, style::Color};
+
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    /// Feed a key which is not expected to exit the repl
+    fn feed<const N: usize>(
+        repl: &mut Repl<N>,
+        executor: &mut impl CommandExecutor,
+        key: KeyEvent,
+    ) {
+        assert_eq!(
+            repl.feed_key_event(executor, key).unwrap(),
+            ControlFlow::Continue(())
+        );
+    }
+
+    fn type_str<const N: usize>(repl: &mut Repl<N>, executor: &mut impl CommandExecutor, s: &str) {
+        for c in s.chars() {
+            feed(repl, executor, key(KeyCode::Char(c)));
+        }
+    }
+
+    #[test]
+    fn ctrl_c_then_submit_ensures_newlines() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.set_ensure_newline(true);
+        repl.emit_prompt();
+        let mut executor = ();
+
+        type_str(&mut repl, &mut executor, "abc");
+        feed(&mut repl, &mut executor, ctrl('c'));
+        type_str(&mut repl, &mut executor, "ls");
+        feed(&mut repl, &mut executor, key(KeyCode::Enter));
+
+        assert_eq!(repl.text(), ">abc^C\n>ls\n");
+        assert_eq!(repl.current_prompt(), ">");
+    }
+
+    #[test]
+    fn history_file_appended_on_submit() {
+        let path = std::env::temp_dir().join(format!("tui_repl_history_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut repl = Repl::new();
+        repl.set_history_file(&path);
+        let read = |path: &std::path::Path| {
+            let file = std::fs::read_to_string(path).unwrap();
+            file.strip_prefix("#tui_repl history v1\n")
+                .unwrap()
+                .to_owned()
+        };
+
+        submit_str(&mut repl, &mut (), "ls");
+        assert_eq!(read(&path), "ls\n");
+        submit_str(&mut repl, &mut (), " ");
+        submit_str(&mut repl, &mut (), "cd");
+        assert_eq!(read(&path), "ls\ncd\n");
+
+        repl.set_history_flush_interval(2);
+        submit_str(&mut repl, &mut (), "a");
+        assert_eq!(read(&path), "ls\ncd\n");
+        submit_str(&mut repl, &mut (), "b");
+        assert_eq!(read(&path), "ls\ncd\na\nb\n");
+        submit_str(&mut repl, &mut (), "c");
+        repl.flush_history_file().unwrap();
+        assert_eq!(read(&path), "ls\ncd\na\nb\nc\n");
+
+        let loaded = History::<32>::load_from_path(&path).unwrap();
+        assert_eq!(&loaded, repl.history());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn legacy_history_file_converted() {
+        let path = std::env::temp_dir().join(format!("tui_repl_legacy_{}", std::process::id()));
+        std::fs::write(&path, "ls\\x\ncd\n").unwrap();
+        let mut repl = Repl::new();
+        repl.set_history_file(&path);
+        submit_str(&mut repl, &mut (), "a");
+        repl.edit(Action::Insert('\n'));
+        submit_str(&mut repl, &mut (), "b");
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "#tui_repl history v1\nls\\\\x\ncd\na\n\\nb\n"
+        );
+        let loaded = History::<32>::load_from_path(&path).unwrap();
+        assert_eq!(
+            loaded.iter_strings().collect::<Vec<_>>(),
+            ["ls\\x", "cd", "a", "\nb"]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn newer_history_file_not_appended() {
+        let path = std::env::temp_dir().join(format!("tui_repl_newer_{}", std::process::id()));
+        std::fs::write(&path, "#tui_repl history v2\nls\n").unwrap();
+        let mut repl = Repl::new();
+        repl.set_history_file(&path);
+        submit_str(&mut repl, &mut (), "a");
+
+        assert_eq!(
+            repl.last_history_error().map(io::Error::kind),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "#tui_repl history v2\nls\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn history_file_write_error_ignored() {
+        let mut repl = Repl::new();
+        repl.set_history_file(std::env::temp_dir().join("tui_repl_missing_dir/history"));
+        assert!(repl.last_history_error().is_none());
+        submit_str(&mut repl, &mut (), "ls");
+        assert_eq!(repl.history().newest(), Some(['l', 's'].as_slice()));
+        assert_eq!(
+            repl.last_history_error().map(io::Error::kind),
+            Some(io::ErrorKind::NotFound)
+        );
+        assert!(repl.flush_history_file().is_err());
+    }
+
+    #[test]
+    fn failed_history_file_writes_bounded() {
+        let mut repl = Repl::<2>::new_with_history(History::new());
+        repl.set_history_file(std::env::temp_dir().join("tui_repl_missing_dir/history"));
+        for command in ["a", "b", "c", "d"] {
+            submit_str(&mut repl, &mut (), command);
+        }
+        let pending = &repl.history_file.as_ref().unwrap().pending;
+        assert_eq!(pending, &[vec!['c'], vec!['d']]);
+    }
+
+    #[test]
+    fn multibyte_text_trimmed_on_char_boundaries() {
+        let mut repl = Repl::new();
+        repl.set_output_sink(SharedBuf::default());
+        repl.set_scrollback_limit(Some(2));
+        repl.print("äöü\n€€\n🦀");
+        assert_eq!(repl.text(), "€€\n🦀");
+
+        // The executor shortens the text, the old end is now inside of a char
+        let mut executor = |_: String, out: &mut String| {
+            out.pop();
+            out.push('€');
+            Ok(())
+        };
+        submit_str(&mut repl, &mut executor, "ü");
+        assert_eq!(repl.text(), "€€\n🦀€");
+        feed(&mut repl, &mut executor, ctrl('c'));
+        assert_eq!(repl.text(), "€€\n🦀€^€");
+    }
+
+    #[test]
+    fn keys_map_to_actions() {
+        let mut repl = Repl::new();
+        assert_eq!(
+            repl.key_to_action(key(KeyCode::Up)),
+            Some(Action::HistoryPrev)
+        );
+        assert_eq!(
+            repl.key_to_action(key(KeyCode::Char('a'))),
+            Some(Action::Insert('a'))
+        );
+        assert_eq!(
+            repl.key_to_action(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::SHIFT)),
+            Some(Action::Insert('A'))
+        );
+        assert_eq!(
+            repl.key_to_action(ctrl('w')),
+            Some(Action::DeleteWordBackward)
+        );
+        assert_eq!(repl.key_to_action(ctrl('g')), Some(Action::Clear));
+        assert_eq!(
+            repl.key_to_action(ctrl(']')),
+            Some(Action::MoveMatchingBracket)
+        );
+        assert_eq!(
+            repl.key_to_action(KeyEvent::new(KeyCode::Up, KeyModifiers::ALT)),
+            Some(Action::MoveLineUp)
+        );
+        assert_eq!(repl.key_to_action(ctrl('7')), Some(Action::Undo));
+        assert_eq!(
+            repl.key_to_action(KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT)),
+            Some(Action::Insert('\n'))
+        );
+
+        assert_eq!(repl.key_to_action(key(KeyCode::Enter)), None);
+        assert_eq!(repl.key_to_action(ctrl('c')), None);
+        assert_eq!(repl.key_to_action(ctrl('d')), None);
+        assert_eq!(repl.key_to_action(key(KeyCode::PageUp)), None);
+        assert_eq!(repl.key_to_action(key(KeyCode::F(1))), None);
+
+        repl.set_submit_key(SubmitKey::CtrlEnter);
+        assert_eq!(
+            repl.key_to_action(key(KeyCode::Enter)),
+            Some(Action::Insert('\n'))
+        );
+    }
+
+    #[test]
+    fn busy_while_executing() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        let busy = repl.busy_handle();
+        let mut executor = |_: String, out: &mut String| {
+            assert!(busy.is_busy());
+            out.push('\n');
+            Ok(())
+        };
+        submit_str(&mut repl, &mut executor, "ls");
+        assert!(!repl.is_busy());
+
+        repl.set_busy_frames(["a", "b"]);
+        repl.busy_handle().set(true);
+        assert!(repl.is_busy());
+        type_str(&mut repl, &mut (), "cd");
+        let area = Rect::new(0, 0, 6, 2);
+        for frame in [">a    ", ">b    ", ">a    "] {
+            let mut buf = Buffer::empty(area);
+            (&mut repl).render(area, &mut buf);
+            assert_eq!(buffer_row(&buf, 1), frame);
+        }
+
+        repl.set_busy(false);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 1), ">cd   ");
+    }
+
+    #[test]
+    fn busy_set_by_executor_kept() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        let busy = repl.busy_handle();
+        let mut executor = |command: String, out: &mut String| {
+            // An asynchronous command which is still running after the executor returned
+            busy.set(command == "sleep");
+            out.push('\n');
+            Ok(())
+        };
+        submit_str(&mut repl, &mut executor, "sleep");
+        assert!(repl.is_busy());
+        submit_str(&mut repl, &mut executor, "ls");
+        assert!(!repl.is_busy());
+    }
+
+    #[test]
+    fn unfocused_pauses_spinner() {
+        let mut repl = Repl::with_text(">");
+        repl.set_busy_frames(["a", "b"]);
+        repl.set_busy(true);
+        let area = Rect::new(0, 0, 4, 1);
+        let render = |repl: &mut Repl<32>| {
+            let mut buf = Buffer::empty(area);
+            repl.render(area, &mut buf);
+            buffer_row(&buf, 0)
+        };
+        assert_eq!(render(&mut repl), ">a  ");
+
+        repl.set_focused(false);
+        assert!(!repl.is_focused());
+        assert_eq!(render(&mut repl), ">b  ");
+        assert_eq!(render(&mut repl), ">b  ");
+
+        repl.set_focused(true);
+        assert!(repl.is_focused());
+        assert_eq!(render(&mut repl), ">b  ");
+        assert_eq!(render(&mut repl), ">a  ");
+    }
+
+    #[test]
+    fn feed_events_until_exit() {
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        let events = "ls -a"
+            .chars()
+            .map(|c| key(KeyCode::Char(c)))
+            .chain([key(KeyCode::Backspace), key(KeyCode::Enter), ctrl('d')])
+            .chain([key(KeyCode::Char('x'))])
+            .map(Event::Key)
+            .chain([Event::Resize(10, 10)]);
+
+        let flow = repl.feed_events(&mut recorder, events).unwrap();
+        assert_eq!(flow, ControlFlow::Break(()));
+        assert_eq!(recorder.0, ["ls -"]);
+        assert_eq!(input_string(&repl), "");
+        assert_eq!(
+            repl.history().newest(),
+            Some("ls -".chars().collect::<Vec<_>>().as_slice())
+        );
+
+        let flow = repl
+            .feed_events(&mut recorder, [Event::Key(key(KeyCode::Char('y')))])
+            .unwrap();
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(input_string(&repl), "y");
+    }
+
+    #[test]
+    fn ctrl_c_sets_interrupted() {
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        assert!(!repl.was_interrupted());
+
+        type_str(&mut repl, &mut recorder, "abc");
+        feed(&mut repl, &mut recorder, ctrl('c'));
+        assert!(repl.was_interrupted());
+
+        submit_str(&mut repl, &mut recorder, "ls");
+        assert!(!repl.was_interrupted());
+        assert_eq!(recorder.0, ["", "ls"]);
+    }
+
+    #[test]
+    fn ensure_newline_keeps_terminated_output() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.set_ensure_newline(true);
+        let mut executor = |cmd: String, out: &mut String| {
+            out.push('\n');
+            out.push_str(&cmd);
+            out.push('\n');
+            Ok(())
+        };
+
+        type_str(&mut repl, &mut executor, "echo");
+        feed(&mut repl, &mut executor, key(KeyCode::Enter));
+
+        assert_eq!(repl.text(), "echo\necho\n");
+    }
+
+    #[test]
+    fn prompt_line_policy() {
+        for (text, policy, row) in [
+            ("out\n", PromptLine::ContinueOutput, 1),
+            ("out\n", PromptLine::NewLine, 1),
+            ("out", PromptLine::ContinueOutput, 0),
+            ("out", PromptLine::NewLine, 1),
+        ] {
+            let mut repl = Repl::new();
+            repl.set_prompt(">");
+            repl.emit_prompt();
+            repl.set_prompt_line(policy);
+            repl.print(text);
+            type_str(&mut repl, &mut (), "ab");
+
+            let area = Rect::new(0, 0, 8, 3);
+            let mut buf = Buffer::empty(area);
+            (&mut repl).render(area, &mut buf);
+            let x = if row == 0 { 6 } else { 3 };
+            assert_eq!(
+                repl.cursor_pos_in(area),
+                (x, row),
+                "{:?} {:?}",
+                text,
+                policy
+            );
+            let expected = if row == 0 { "out>ab  " } else { ">ab     " };
+            assert_eq!(buffer_row(&buf, row), expected);
+
+            let mut recorder = Recorder::default();
+            feed(&mut repl, &mut recorder, key(KeyCode::Enter));
+            let echoed = if row == 0 { "out>ab" } else { "out\n>ab" };
+            assert_eq!(repl.text(), echoed);
+        }
+    }
+
+    #[test]
+    fn missing_newline_marked() {
+        let mut executor = |cmd: String, out: &mut String| {
+            out.push('\n');
+            out.push_str(&cmd);
+            Ok(())
+        };
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.set_ensure_newline(true);
+        repl.emit_prompt();
+        submit_str(&mut repl, &mut executor, "ab");
+        assert_eq!(repl.text(), "> ab\nab\n");
+        assert_eq!(repl.cursor_pos_in(Rect::new(0, 0, 10, 5)), (2, 2));
+
+        repl.set_missing_newline_marker("%");
+        submit_str(&mut repl, &mut executor, "cd");
+        submit_str(&mut repl, &mut executor, "");
+        assert_eq!(repl.text(), "> ab\nab\n> cd\ncd%\n> \n");
+        assert_eq!(repl.cursor_pos_in(Rect::new(0, 0, 10, 8)), (2, 5));
+
+        repl.remove_missing_newline_marker();
+        submit_str(&mut repl, &mut executor, "e");
+        assert!(repl.text().ends_with("> e\ne\n"));
+    }
+
+    #[test]
+    fn newline_not_ensured_by_default() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        let mut executor = ();
+
+        type_str(&mut repl, &mut executor, "ab");
+        feed(&mut repl, &mut executor, ctrl('c'));
+
+        assert_eq!(repl.text(), "ab^C");
+        assert_eq!(repl.current_prompt(), ">");
+    }
+
+    #[test]
+    fn input_rendered_with_input_style() {
+        let mut repl = Repl::new();
+        repl.text_mut().push_str("out\n>");
+        let style = Style::default()
+            .fg(tui::style::Color::Yellow)
+            .add_modifier(tui::style::Modifier::BOLD);
+        repl.set_input_style(style);
+        type_str(&mut repl, &mut (), "ls");
+
+        let area = Rect::new(0, 0, 10, 3);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+
+        let plain = tui::buffer::Cell::default().style();
+        assert_eq!(buf.get(0, 0).symbol, "o");
+        assert_eq!(buf.get(0, 0).style(), plain);
+        assert_eq!(buf.get(0, 1).symbol, ">");
+        assert_eq!(buf.get(0, 1).style(), plain);
+        assert_eq!(buf.get(1, 1).symbol, "l");
+        assert_eq!(buf.get(1, 1).style(), plain.patch(style));
+        assert_eq!(buf.get(2, 1).symbol, "s");
+        assert_eq!(buf.get(2, 1).style(), plain.patch(style));
+        assert_eq!(repl.text(), "out\n>");
+    }
+
+    /// Executor recording every executed command
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl CommandExecutor for Recorder {
+        fn execute(&mut self, command: String, _repl_buffer: &mut String) -> io::Result<()> {
+            self.0.push(command);
+            Ok(())
+        }
+    }
+
+    fn submit_str<const N: usize>(
+        repl: &mut Repl<N>,
+        executor: &mut impl CommandExecutor,
+        s: &str,
+    ) {
+        type_str(repl, executor, s);
+        feed(repl, executor, key(KeyCode::Enter));
+    }
+
+    #[test]
+    fn alias_expanded_with_arguments() {
+        let mut repl = Repl::new();
+        repl.add_alias("ll", "ls -la");
+        let mut recorder = Recorder::default();
+
+        submit_str(&mut repl, &mut recorder, "ll");
+        submit_str(&mut repl, &mut recorder, "ll /tmp  -h");
+        submit_str(&mut repl, &mut recorder, "lll");
+        submit_str(&mut repl, &mut recorder, "echo ll");
+
+        assert_eq!(recorder.0, ["ls -la", "ls -la /tmp  -h", "lll", "echo ll"]);
+        assert_eq!(repl.text(), "llll /tmp  -hlllecho ll");
+    }
+
+    #[test]
+    fn alias_history_entry_configurable() {
+        let mut repl = Repl::new();
+        repl.add_alias("ll", "ls -la");
+        submit_str(&mut repl, &mut (), "ll");
+        repl.set_store_expanded_aliases(true);
+        submit_str(&mut repl, &mut (), "ll");
+
+        assert_eq!(
+            repl.history().iter().collect::<Vec<_>>(),
+            ["ll", "ls -la"].map(|s| s.chars().collect::<Vec<_>>())
+        );
+    }
+
+    #[test]
+    fn submitted_command_echo_configurable() {
+        let mut executor = |cmd: String, out: &mut String| {
+            out.push_str(&cmd.to_uppercase());
+            out.push('\n');
+            Ok(())
+        };
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.emit_prompt();
+        submit_str(&mut repl, &mut executor, "ab");
+        assert_eq!(repl.text(), "> abAB\n");
+
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.emit_prompt();
+        repl.set_echo_submitted_command(false);
+        submit_str(&mut repl, &mut executor, "ab");
+        submit_str(&mut repl, &mut executor, "cd");
+        assert_eq!(repl.text(), "AB\nCD\n");
+    }
+
+    fn git_completer(input: &[char], cursor: usize) -> Vec<String> {
+        let word = input[complete::word_start(input, cursor)..cursor]
+            .iter()
+            .collect::<String>();
+        ["checkout", "cherry-pick", "commit"]
+            .into_iter()
+            .filter(|command| command.starts_with(&word))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn completion_menu_inserts_selected() {
+        let mut repl = Repl::new();
+        repl.set_completer(git_completer);
+        type_str(&mut repl, &mut (), "git ch");
+        feed(&mut repl, &mut (), key(KeyCode::Tab));
+        let menu = repl.completion_menu().unwrap();
+        assert_eq!(menu.candidates(), ["checkout", "cherry-pick"]);
+        assert_eq!(menu.selected(), 0);
+
+        let area = Rect::new(0, 0, 20, 3);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "git ch              ");
+        assert_eq!(buffer_row(&buf, 1), "    checkout        ");
+        assert_eq!(buffer_row(&buf, 2), "    cherry-pick     ");
+        let reversed = Style::default().add_modifier(Modifier::REVERSED);
+        assert_eq!(buf.get(4, 1).style(), buf.get(4, 1).style().patch(reversed));
+        assert_ne!(buf.get(4, 2).style(), buf.get(4, 2).style().patch(reversed));
+
+        feed(&mut repl, &mut (), key(KeyCode::Down));
+        assert_eq!(repl.completion_menu().unwrap().selected(), 1);
+        feed(&mut repl, &mut (), key(KeyCode::Down));
+        feed(&mut repl, &mut (), key(KeyCode::Up));
+        feed(&mut repl, &mut (), key(KeyCode::Enter));
+        assert!(repl.completion_menu().is_none());
+        assert_eq!(input_string(&repl), "git cherry-pick");
+
+        feed(&mut repl, &mut (), key(KeyCode::Char('z')));
+        assert_eq!(input_string(&repl), "git cherry-pickz");
+    }
+
+    #[test]
+    fn completion_menu_closes() {
+        let mut repl = Repl::new();
+        repl.set_completer(git_completer);
+        type_str(&mut repl, &mut (), "c");
+        feed(&mut repl, &mut (), key(KeyCode::Tab));
+        assert_eq!(repl.completion_menu().unwrap().candidates().len(), 3);
+        feed(&mut repl, &mut (), key(KeyCode::Esc));
+        assert!(repl.completion_menu().is_none());
+        assert_eq!(input_string(&repl), "c");
+
+        feed(&mut repl, &mut (), key(KeyCode::Tab));
+        type_str(&mut repl, &mut (), "om");
+        assert!(repl.completion_menu().is_none());
+        assert_eq!(input_string(&repl), "com");
+
+        // A single candidate is inserted right away
+        feed(&mut repl, &mut (), key(KeyCode::Tab));
+        assert!(repl.completion_menu().is_none());
+        assert_eq!(input_string(&repl), "commit");
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mut repl = Repl::with_text("hi\n");
+        repl.set_prompt("> ");
+        repl.emit_prompt();
+        submit_str(&mut repl, &mut (), "one");
+        repl.emit_prompt();
+        submit_str(&mut repl, &mut (), "two");
+        repl.emit_prompt();
+        type_str(&mut repl, &mut (), "thr");
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+
+        let snapshot = repl.snapshot();
+        let mut restored = Repl::new();
+        restored.restore(snapshot.clone());
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.text(), repl.text());
+        assert_eq!(restored.prompt(), "> ");
+        assert_eq!(input_string(&restored), "thr");
+        assert_eq!(restored.cursor_char_index(), 2);
+        assert_eq!(
+            restored.history().iter_strings().collect::<Vec<_>>(),
+            ["one", "two"]
+        );
+
+        restored.restore(ReplSnapshot {
+            input: "ab".into(),
+            cursor: 10,
+            ..snapshot
+        });
+        assert_eq!(restored.cursor_char_index(), 2);
+    }
+
+    #[test]
+    fn restored_history_not_pushed() {
+        let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut repl = Repl::<2>::new_with_history(History::new());
+        let sink = evicted.clone();
+        repl.history_mut()
+            .set_on_evict(move |entry| sink.lock().unwrap().push(entry));
+        for command in ["a", "b", "c"] {
+            submit_str(&mut repl, &mut (), command);
+        }
+
+        let mut snapshot = repl.snapshot();
+        snapshot.history = ["x", "y", " "].map(String::from).to_vec();
+        repl.restore(snapshot);
+        assert_eq!(
+            repl.history().iter_strings().collect::<Vec<_>>(),
+            ["y", " "]
+        );
+        assert_eq!(repl.history().entry_number(0), Some(1));
+        assert_eq!(*evicted.lock().unwrap(), [vec!['a']]);
+    }
+
+    #[test]
+    fn last_command_includes_filtered() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.last_command(), None);
+        submit_str(&mut repl, &mut (), "ls");
+        assert_eq!(repl.last_command(), Some(&['l', 's'][..]));
+
+        submit_str(&mut repl, &mut (), "  ");
+        assert_eq!(repl.last_command(), Some(&[' ', ' '][..]));
+        assert_eq!(repl.history().newest(), Some(&['l', 's'][..]));
+
+        let mut repl = Repl::<0>::new_with_history(History::new());
+        submit_str(&mut repl, &mut (), "pwd");
+        assert_eq!(repl.last_command(), Some(&['p', 'w', 'd'][..]));
+        assert!(repl.history().is_empty());
+    }
+
+    #[test]
+    fn repeat_last_command_executes_again() {
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        submit_str(&mut repl, &mut recorder, "make");
+        feed(&mut repl, &mut recorder, ctrl('p'));
+        assert_eq!(recorder.0, ["make"]);
+
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        repl.set_repeat_key(true);
+        feed(&mut repl, &mut recorder, ctrl('p'));
+        assert!(recorder.0.is_empty());
+        assert!(repl.take_bell());
+
+        submit_str(&mut repl, &mut recorder, "make");
+        type_str(&mut repl, &mut recorder, "ca");
+        feed(&mut repl, &mut recorder, ctrl('p'));
+        assert_eq!(recorder.0, ["make", "make"]);
+        assert_eq!(input_string(&repl), "ca");
+        assert_eq!(repl.history().len(), 2);
+
+        repl.set_skip_repeat_history(true);
+        feed(&mut repl, &mut recorder, ctrl('p'));
+        assert_eq!(recorder.0, ["make", "make", "make"]);
+        assert_eq!(repl.history().len(), 2);
+    }
+
+    #[test]
+    fn returned_output_appended() {
+        let mut appending = |cmd: String, out: &mut String| {
+            out.push_str(&cmd.to_uppercase());
+            out.push_str("\n>");
+            Ok(())
+        };
+        let mut returning = ReturnOutput(|cmd: String| Ok(format!("{}\n>", cmd.to_uppercase())));
+
+        let mut repl = Repl::with_text(">");
+        let mut returned = Repl::with_text(">");
+        for command in ["ls", "", "cd x"] {
+            submit_str(&mut repl, &mut appending, command);
+            submit_str(&mut returned, &mut returning, command);
+        }
+        assert_eq!(repl.text(), ">lsLS\n>\n>cd xCD X\n>");
+        assert_eq!(returned.text(), repl.text());
+    }
+
+    #[test]
+    fn fold_command_output() {
+        let mut executor = |cmd: String, out: &mut String| {
+            for i in 0..cmd.len() {
+                out.push_str(&format!("\n{}", i));
+            }
+            out.push('\n');
+            Ok(())
+        };
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.emit_prompt();
+        submit_str(&mut repl, &mut executor, "abc");
+        submit_str(&mut repl, &mut executor, "de");
+        assert_eq!(repl.block_count(), 2);
+        let area = Rect::new(0, 0, 16, 8);
+        let render = |repl: &mut Repl<32>| {
+            let mut buf = Buffer::empty(area);
+            repl.render(area, &mut buf);
+            (0..area.height)
+                .map(|y| buffer_row(&buf, y).trim_end().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            render(&mut repl),
+            ["> abc", "0", "1", "2", "> de", "0", "1", ">"]
+        );
+
+        repl.set_folded(0, true);
+        assert_eq!(
+            render(&mut repl),
+            ["> abc [+3 lines]", "> de", "0", "1", ">", "", "", ""]
+        );
+        assert_eq!(repl.cursor_pos_in(area), (2, 4));
+
+        feed(&mut repl, &mut executor, ctrl('o'));
+        assert!(repl.is_folded(1));
+        assert_eq!(
+            render(&mut repl),
+            [
+                "> abc [+3 lines]",
+                "> de [+2 lines]",
+                ">",
+                "",
+                "",
+                "",
+                "",
+                ""
+            ]
+        );
+        assert!(repl.text().ends_with("0\n1\n"));
+
+        repl.set_all_folded(false);
+        assert_eq!(render(&mut repl)[1], "0");
+    }
+
+    #[test]
+    fn executor_clearing_text_forgets_blocks() {
+        let mut executor = |cmd: String, out: &mut String| {
+            match cmd.as_str() {
+                "clear" => out.truncate(0),
+                _ => out.push_str("\n1\n2\n3\n4\n5\n"),
+            }
+            Ok(())
+        };
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.emit_prompt();
+        submit_str(&mut repl, &mut executor, "a long command");
+        submit_str(&mut repl, &mut executor, "b");
+        repl.set_folded(1, true);
+        submit_str(&mut repl, &mut executor, "clear");
+        assert_eq!(repl.text(), "");
+        assert_eq!(repl.block_count(), 0);
+
+        // A cleared text refilled past the old length
+        submit_str(&mut repl, &mut executor, "c");
+        repl.set_all_folded(true);
+        let len = repl.text().len();
+        let mut refill = |_: String, out: &mut String| {
+            *out = "x".repeat(len + 10);
+            Ok(())
+        };
+        submit_str(&mut repl, &mut refill, "d");
+        assert_eq!(repl.block_count(), 0);
+        assert_eq!(repl.command_blocks().count(), 0);
+
+        let area = Rect::new(0, 0, 10, 4);
+        let mut buf = Buffer::empty(area);
+        repl.set_page_height(area.height);
+        (&mut repl).render(area, &mut buf);
+        repl.jump_to_prev_prompt();
+    }
+
+    #[test]
+    fn command_blocks_split_text() {
+        let mut executor = |cmd: String, out: &mut String| {
+            out.push_str(&format!("\n{}\n", cmd.to_uppercase()));
+            Ok(())
+        };
+        let mut repl = Repl::with_text("hello\n");
+        repl.set_prompt("> ");
+        repl.emit_prompt();
+        assert_eq!(repl.command_blocks().count(), 0);
+        submit_str(&mut repl, &mut executor, "ls");
+        repl.print("late\n");
+        submit_str(&mut repl, &mut executor, "pwd");
+
+        let blocks = repl.command_blocks().collect::<Vec<_>>();
+        assert_eq!(
+            blocks,
+            [
+                CommandBlock {
+                    prompt: "> ",
+                    command: "ls",
+                    output: "\nLS\nlate\n",
+                    folded: false,
+                },
+                CommandBlock {
+                    prompt: "> ",
+                    command: "pwd",
+                    output: "\nPWD\n",
+                    folded: false,
+                },
+            ]
+        );
+        assert_eq!(repl.command_block(2), None);
+
+        repl.set_scrollback_limit(Some(4));
+        assert_eq!(repl.command_blocks().count(), 1);
+        assert_eq!(repl.command_block(0).unwrap().command, "pwd");
+    }
+
+    #[test]
+    fn jump_between_prompts() {
+        let mut executor = |_: String, out: &mut String| {
+            out.push_str("\n1\n2\n3\n");
+            Ok(())
+        };
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.emit_prompt();
+        for command in ["a", "b", "c"] {
+            submit_str(&mut repl, &mut executor, command);
+        }
+        repl.set_page_height(4);
+
+        let mut offsets = Vec::new();
+        for _ in 0..4 {
+            feed(
+                &mut repl,
+                &mut executor,
+                KeyEvent::new(KeyCode::Up, KeyModifiers::CONTROL),
+            );
+            offsets.push(repl.scroll_offset());
+        }
+        assert_eq!(offsets, [1, 5, 9, 9]);
+        assert!(repl.take_bell());
+
+        offsets.clear();
+        for _ in 0..4 {
+            feed(
+                &mut repl,
+                &mut executor,
+                KeyEvent::new(KeyCode::Down, KeyModifiers::CONTROL),
+            );
+            offsets.push(repl.scroll_offset());
+        }
+        assert_eq!(offsets, [5, 1, 0, 0]);
+        assert!(repl.take_bell());
+
+        repl.set_folded(1, true);
+        feed(
+            &mut repl,
+            &mut executor,
+            KeyEvent::new(KeyCode::Up, KeyModifiers::CONTROL),
+        );
+        feed(
+            &mut repl,
+            &mut executor,
+            KeyEvent::new(KeyCode::Up, KeyModifiers::CONTROL),
+        );
+        assert_eq!(repl.scroll_offset(), 2);
+    }
+
+    #[test]
+    fn jump_to_first_prompt_folded() {
+        let mut executor = |_: String, out: &mut String| {
+            out.push_str("\n1\n2\n3\n");
+            Ok(())
+        };
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.emit_prompt();
+        for command in ["a", "b"] {
+            submit_str(&mut repl, &mut executor, command);
+        }
+        repl.set_page_height(2);
+        repl.set_folded(0, true);
+        assert_eq!(repl.prompt_rows(), [0, 1]);
+
+        let ctrl_up = KeyEvent::new(KeyCode::Up, KeyModifiers::CONTROL);
+        feed(&mut repl, &mut executor, ctrl_up);
+        feed(&mut repl, &mut executor, ctrl_up);
+        assert_eq!(repl.scroll_offset(), 4);
+        assert!(!repl.take_bell());
+    }
+
+    #[test]
+    fn comments_not_executed() {
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        submit_str(&mut repl, &mut recorder, "# note");
+        assert_eq!(repl.history().len(), 1);
+
+        repl.set_comment_prefix("#");
+        submit_str(&mut repl, &mut recorder, "  # just a note");
+        submit_str(&mut repl, &mut recorder, "ls # listing");
+
+        assert_eq!(recorder.0, ["# note", "ls # listing"]);
+        assert_eq!(
+            repl.history().iter().collect::<Vec<_>>(),
+            ["# note", "ls # listing"].map(|s| s.chars().collect::<Vec<_>>())
+        );
+        assert!(repl.text().contains("  # just a note\n"));
+
+        repl.remove_comment_prefix();
+        assert_eq!(repl.comment_prefix(), None);
+        submit_str(&mut repl, &mut recorder, "# again");
+        assert_eq!(recorder.0, ["# note", "ls # listing", "# again"]);
+
+        let mut repl = Repl::new();
+        repl.set_comment_prefix("#");
+        repl.set_echo_submitted_command(false);
+        submit_str(&mut repl, &mut recorder, "# hidden");
+        assert_eq!(repl.text(), "");
+    }
+
+    #[test]
+    fn removed_alias_not_expanded() {
+        let mut repl = Repl::new();
+        repl.add_alias("ll", "ls -la");
+        assert_eq!(repl.remove_alias("ll"), Some(String::from("ls -la")));
+        assert_eq!(repl.expand_alias("ll"), None);
+    }
+
+    #[test]
+    fn input_height_counts_rows() {
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.emit_prompt();
+        assert_eq!(repl.input_height(10), 1);
+        type_str(&mut repl, &mut (), "ls -la");
+        assert_eq!(repl.input_height(10), 1);
+
+        type_str(&mut repl, &mut (), "\n\nb");
+        assert_eq!(repl.input_height(10), 3);
+
+        repl.set_input("0123456789abcdefghij");
+        assert_eq!(repl.input_height(10), 3);
+        assert_eq!(repl.input_height(22), 1);
+        assert_eq!(repl.input_height(0), 22);
+    }
+
+    #[test]
+    fn absolute_cursor_pos_adds_origin() {
+        let mut repl = Repl::new();
+        repl.text_mut().push_str("out\n>");
+        type_str(&mut repl, &mut (), "ab\ncd");
+        let area = Rect::new(3, 7, 20, 5);
+
+        let (x, y) = repl.cursor_pos_in(area);
+        assert_eq!((x, y), (2, 2));
+        assert_eq!(repl.cursor_pos_in_absolute(area), (x + 3, y + 7));
+    }
+
+    #[test]
+    fn set_input_places_cursor_at_end() {
+        let mut repl = Repl::new();
+        repl.text_mut().push('>');
+        type_str(&mut repl, &mut (), "abc");
+        feed(&mut repl, &mut (), key(KeyCode::Home));
+
+        repl.set_input("git commit");
+        assert_eq!(
+            repl.current_input().iter().collect::<String>(),
+            "git commit"
+        );
+        assert_eq!(repl.cursor_pos_in(Rect::new(0, 0, 20, 5)), (11, 0));
+
+        type_str(&mut repl, &mut (), " -a");
+        assert_eq!(
+            repl.current_input().iter().collect::<String>(),
+            "git commit -a"
+        );
+    }
+
+    #[test]
+    fn repl_is_send_and_sync() {
+        fn is_send_and_sync<T: Send + Sync>() {}
+        is_send_and_sync::<Repl<32>>();
+    }
+
+    #[test]
+    fn input_change_callback_fires_per_change() {
+        use std::sync::Mutex;
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let mut repl = Repl::new();
+        let collector = Arc::clone(&changes);
+        repl.set_on_input_change(move |input| {
+            collector
+                .lock()
+                .unwrap()
+                .push(input.iter().collect::<String>())
+        });
+
+        type_str(&mut repl, &mut (), "abc");
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+        feed(&mut repl, &mut (), key(KeyCode::Backspace));
+
+        assert_eq!(*changes.lock().unwrap(), ["a", "ab", "abc", "ac"]);
+    }
+
+    #[test]
+    fn highlighter_styles_keyword() {
+        let keyword = Style::default().fg(tui::style::Color::Blue);
+        let mut repl = Repl::new();
+        repl.set_highlighter(move |input: &[char]| {
+            let input = input.iter().collect::<String>();
+            input
+                .match_indices("let")
+                .map(|(idx, m)| (idx..idx + m.len(), keyword))
+                .collect()
+        });
+        type_str(&mut repl, &mut (), "x let");
+
+        let area = Rect::new(0, 0, 10, 1);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+
+        let plain = tui::buffer::Cell::default().style();
+        let styles = (0..6).map(|x| buf.get(x, 0).style()).collect::<Vec<_>>();
+        assert_eq!(
+            styles,
+            [
+                plain,
+                plain,
+                plain.patch(keyword),
+                plain.patch(keyword),
+                plain.patch(keyword),
+                plain
+            ]
+        );
+    }
+
+    /// Event source replaying events, `None` is a poll timing out
+    struct ScriptedEvents(std::collections::VecDeque<Option<Event>>);
+
+    impl ScriptedEvents {
+        fn new(events: impl IntoIterator<Item = Option<Event>>) -> Self {
+            Self(events.into_iter().collect())
+        }
+    }
+
+    impl EventSource for ScriptedEvents {
+        fn poll(&mut self, _timeout: Duration) -> io::Result<bool> {
+            match self.0.front() {
+                Some(Some(_)) => Ok(true),
+                Some(None) => {
+                    self.0.pop_front();
+                    Ok(false)
+                }
+                None => Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "no events left",
+                )),
+            }
+        }
+
+        fn read(&mut self) -> io::Result<Event> {
+            self.0
+                .pop_front()
+                .flatten()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "no event ready"))
+        }
+    }
+
+    fn test_terminal(width: u16, height: u16) -> Terminal<TestBackend> {
+        Terminal::new(TestBackend::new(width, height)).unwrap()
+    }
+
+    fn row(term: &Terminal<TestBackend>, y: u16) -> String {
+        buffer_row(term.backend().buffer(), y)
+    }
+
+    fn buffer_row(buf: &Buffer, y: u16) -> String {
+        (0..buf.area.width)
+            .map(|x| buf.get(x, y).symbol.as_str())
+            .collect()
+    }
+
+    fn step<const N: usize>(
+        repl: &mut Repl<N>,
+        term: &mut Terminal<TestBackend>,
+        events: &mut impl EventSource,
+    ) {
+        assert_eq!(
+            repl.step(term, &mut (), events, Duration::ZERO).unwrap(),
+            ControlFlow::Continue(())
+        );
+    }
+
+    #[test]
+    fn redraws_without_events_when_requested() {
+        let mut term = test_terminal(10, 2);
+        let mut repl = Repl::new();
+        let mut events = ScriptedEvents::new([None, None, None]);
+
+        repl.text_mut().push_str("start");
+        repl.request_redraw();
+        step(&mut repl, &mut term, &mut events);
+        assert_eq!(row(&term, 0), "start     ");
+
+        // Not redrawn without a request
+        repl.text_mut().push_str(" x");
+        step(&mut repl, &mut term, &mut events);
+        assert_eq!(row(&term, 0), "start     ");
+
+        let handle = repl.redraw_handle();
+        std::thread::spawn(move || handle.request()).join().unwrap();
+        step(&mut repl, &mut term, &mut events);
+        assert_eq!(row(&term, 0), "start x   ");
+    }
+
+    #[test]
+    fn run_with_events_exits_on_exit_key() {
+        let mut term = test_terminal(10, 2);
+        let mut repl = Repl::new();
+        let mut events = ScriptedEvents::new([
+            Some(Event::Key(key(KeyCode::Char('a')))),
+            None,
+            Some(Event::Key(ctrl('d'))),
+        ]);
+
+        repl.run_with_events(&mut term, &mut (), &mut events, &mut io::sink())
+            .unwrap();
+        assert_eq!(repl.current_input(), ['a']);
+        assert_eq!(row(&term, 0), "a         ");
+    }
+
+    #[test]
+    fn history_recall_places_cursor_at_end() {
+        let mut repl = Repl::new();
+        let area = Rect::new(0, 0, 20, 5);
+        submit_str(&mut repl, &mut (), "a");
+        submit_str(&mut repl, &mut (), "longer");
+        repl.text_mut().clear();
+
+        type_str(&mut repl, &mut (), "abc");
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+        feed(&mut repl, &mut (), key(KeyCode::Up));
+        assert_eq!(repl.cursor_pos_in(area), (6, 0));
+        feed(&mut repl, &mut (), key(KeyCode::Home));
+        feed(&mut repl, &mut (), key(KeyCode::Up));
+        assert_eq!(repl.cursor_pos_in(area), (1, 0));
+        feed(&mut repl, &mut (), key(KeyCode::Home));
+        feed(&mut repl, &mut (), key(KeyCode::Down));
+        assert_eq!(repl.cursor_pos_in(area), (6, 0));
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+        feed(&mut repl, &mut (), key(KeyCode::Down));
+        assert_eq!(repl.cursor_pos_in(area), (0, 0));
+    }
+
+    #[test]
+    fn print_appends_and_trims() {
+        let mut repl = Repl::new();
+        repl.print(">");
+        repl.println("a");
+        repl.println("b");
+        assert_eq!(repl.text(), ">a\nb\n");
+        assert!(repl.redraw_handle().is_requested());
+
+        repl.set_scrollback_limit(Some(2));
+        assert_eq!(repl.text(), "b\n");
+        repl.println("c");
+        repl.print("d");
+        assert_eq!(repl.text(), "c\nd");
+
+        repl.set_prompt("\n>");
+        submit_str(&mut repl, &mut (), "ls");
+        assert_eq!(repl.text(), "c\ndls");
+        assert_eq!(repl.current_prompt(), "\n>");
+    }
+
+    fn input_string<const N: usize>(repl: &Repl<N>) -> String {
+        repl.current_input().iter().collect()
+    }
+
+    const PASTE: &str = "one\ntwo\nthr";
+
+    #[test]
+    fn paste_submits_complete_lines() {
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        repl.feed_paste(&mut recorder, PASTE).unwrap();
+        assert_eq!(recorder.0, ["one", "two"]);
+        assert_eq!(input_string(&repl), "thr");
+    }
+
+    #[test]
+    fn paste_submits_on_final_newline() {
+        let mut repl = Repl::new();
+        repl.set_paste_policy(PastePolicy::SubmitOnFinalNewline);
+        let mut recorder = Recorder::default();
+        repl.feed_paste(&mut recorder, PASTE).unwrap();
+        assert!(recorder.0.is_empty());
+        assert_eq!(input_string(&repl), PASTE);
+
+        repl.current_input_mut().clear();
+        repl.feed_paste(&mut recorder, "one\ntwo\n").unwrap();
+        assert_eq!(recorder.0, ["one", "two"]);
+        assert_eq!(input_string(&repl), "");
+    }
+
+    #[test]
+    fn paste_normalizes_newlines() {
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        repl.feed_paste(&mut recorder, "one\r\ntwo\rthr").unwrap();
+        assert_eq!(recorder.0, ["one", "two"]);
+        assert_eq!(input_string(&repl), "thr");
+
+        repl.set_paste_policy(PastePolicy::NeverSubmit);
+        repl.feed_paste(&mut recorder, "\r\nfour\r").unwrap();
+        feed(&mut repl, &mut recorder, key(KeyCode::Char('\r')));
+        assert_eq!(input_string(&repl), "thr\nfour\n\n");
+
+        repl.set_newline_policy(NewlinePolicy::Keep);
+        repl.current_input_mut().clear();
+        repl.feed_paste(&mut recorder, "a\r\nb").unwrap();
+        assert_eq!(input_string(&repl), "a\r\nb");
+    }
+
+    #[test]
+    fn control_chars_dropped() {
+        let mut repl = Repl::new();
+        type_str(&mut repl, &mut (), "a\u{1b}\u{7}b\tc");
+        assert_eq!(input_string(&repl), "ab\tc");
+
+        repl.set_allow_control_chars(true);
+        type_str(&mut repl, &mut (), "\u{7}");
+        assert_eq!(input_string(&repl), "ab\tc\u{7}");
+    }
+
+    #[test]
+    fn paste_never_submits() {
+        let mut repl = Repl::new();
+        repl.set_paste_policy(PastePolicy::NeverSubmit);
+        let mut recorder = Recorder::default();
+        type_str(&mut repl, &mut recorder, "<>");
+        feed(&mut repl, &mut recorder, key(KeyCode::Left));
+        repl.feed_paste(&mut recorder, PASTE).unwrap();
+        assert!(recorder.0.is_empty());
+        assert_eq!(input_string(&repl), "<one\ntwo\nthr>");
+        assert_eq!(repl.cursor_pos_in(Rect::new(0, 0, 10, 5)), (3, 2));
+
+        let area = Rect::new(0, 0, 6, 2);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buf, Buffer::with_lines(vec!["two   ", "thr>  "]));
+        assert_eq!(repl.cursor_pos_in(area), (3, 1));
+    }
+
+    #[test]
+    fn cursor_on_last_visible_row_when_text_overflows() {
+        let mut repl = Repl::new();
+        repl.text_mut().push_str("a\nb\nc\n>");
+        type_str(&mut repl, &mut (), "ls");
+        let area = Rect::new(0, 0, 4, 2);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buf, Buffer::with_lines(vec!["c   ", ">ls "]));
+        assert_eq!(repl.cursor_pos_in(area), (3, 1));
+    }
+
+    #[test]
+    fn borrowed_executor_usable_after_loop() {
+        let mut term = test_terminal(10, 2);
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        let mut events = ScriptedEvents::new(
+            "ab\nc\n"
+                .chars()
+                .map(|c| match c {
+                    '\n' => key(KeyCode::Enter),
+                    c => key(KeyCode::Char(c)),
+                })
+                .chain([ctrl('q')])
+                .map(|key| Some(Event::Key(key))),
+        );
+
+        repl.run_with_events(&mut term, &mut recorder, &mut events, &mut io::sink())
+            .unwrap();
+        assert_eq!(recorder.0, ["ab", "c"]);
+    }
+
+    #[test]
+    fn prompt_provider_changes_prompt() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        let mut count = 0;
+        repl.set_prompt_provider(move |repl| {
+            count += 1;
+            format!("\n{}/{}>", count, repl.history().len())
+        });
+        repl.emit_prompt();
+        submit_str(&mut repl, &mut (), "a");
+        submit_str(&mut repl, &mut (), "b");
+        assert_eq!(repl.text(), "\n1/0>a\n2/1>b");
+        assert_eq!(repl.current_prompt(), "\n3/2>");
+
+        let mut term = test_terminal(6, 1);
+        repl.draw(&mut term).unwrap();
+        assert_eq!(row(&term, 0), "3/2>  ");
+
+        repl.remove_prompt_provider();
+        submit_str(&mut repl, &mut (), "c");
+        assert!(repl.text().ends_with("3/2>c"));
+        assert_eq!(repl.current_prompt(), ">");
+    }
+
+    #[test]
+    fn bell_at_history_ends() {
+        let mut repl = Repl::new();
+        feed(&mut repl, &mut (), key(KeyCode::Up));
+        assert!(repl.take_bell());
+
+        submit_str(&mut repl, &mut (), "a");
+        submit_str(&mut repl, &mut (), "b");
+        feed(&mut repl, &mut (), key(KeyCode::Up));
+        feed(&mut repl, &mut (), key(KeyCode::Up));
+        assert!(!repl.bell_pending());
+        feed(&mut repl, &mut (), key(KeyCode::Up));
+        assert!(repl.take_bell());
+        assert_eq!(input_string(&repl), "a");
+
+        feed(&mut repl, &mut (), key(KeyCode::Down));
+        feed(&mut repl, &mut (), key(KeyCode::Down));
+        assert!(!repl.bell_pending());
+        assert_eq!(input_string(&repl), "");
+        feed(&mut repl, &mut (), key(KeyCode::Down));
+        assert!(repl.bell_pending());
+
+        let mut out = Vec::new();
+        repl.flush_terminal_effects(&mut out).unwrap();
+        assert_eq!(out, b"\x07");
+        out.clear();
+        repl.flush_terminal_effects(&mut out).unwrap();
+        assert!(out.is_empty());
+    }
+
+    fn word_key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn word_movement_by_class() {
+        for (class, positions) in [
+            (WordClass::Whitespace, vec![8, 0]),
+            (WordClass::Punctuation, vec![8, 4, 3, 0]),
+        ] {
+            let mut repl = Repl::new();
+            repl.set_word_class(class);
+            type_str(&mut repl, &mut (), "foo.bar baz");
+            let mut landed = Vec::new();
+            for _ in &positions {
+                feed(&mut repl, &mut (), word_key(KeyCode::Left));
+                landed.push(repl.cursor_pos_in(Rect::new(0, 0, 20, 1)).0);
+            }
+            assert_eq!(landed, positions);
+
+            feed(&mut repl, &mut (), word_key(KeyCode::Right));
+            let first_end = if class == WordClass::Whitespace { 7 } else { 3 };
+            assert_eq!(repl.cursor_pos_in(Rect::new(0, 0, 20, 1)).0, first_end);
+        }
+    }
+
+    #[test]
+    fn word_deletion() {
+        let mut repl = Repl::new();
+        repl.set_word_class(WordClass::Punctuation);
+        type_str(&mut repl, &mut (), "foo.bar baz");
+        feed(&mut repl, &mut (), ctrl('w'));
+        assert_eq!(input_string(&repl), "foo.bar ");
+        feed(&mut repl, &mut (), word_key(KeyCode::Backspace));
+        assert_eq!(input_string(&repl), "foo.");
+
+        repl.set_input("foo.bar baz");
+        feed(&mut repl, &mut (), key(KeyCode::Home));
+        feed(&mut repl, &mut (), word_key(KeyCode::Delete));
+        assert_eq!(input_string(&repl), ".bar baz");
+        repl.set_word_class(WordClass::Whitespace);
+        feed(&mut repl, &mut (), word_key(KeyCode::Delete));
+        assert_eq!(input_string(&repl), " baz");
+        assert_eq!(repl.cursor_pos_in(Rect::new(0, 0, 20, 1)).0, 0);
+    }
+
+    #[test]
+    fn input_predicates() {
+        let mut repl = Repl::new();
+        assert!(!repl.has_input());
+        assert!(!repl.is_dirty());
+
+        type_str(&mut repl, &mut (), "ls");
+        assert!(repl.has_input());
+        assert!(repl.is_dirty());
+
+        feed(&mut repl, &mut (), key(KeyCode::Enter));
+        assert!(!repl.has_input());
+        assert!(!repl.is_dirty());
+
+        feed(&mut repl, &mut (), key(KeyCode::Up));
+        assert!(repl.has_input());
+        assert!(!repl.is_dirty());
+        type_str(&mut repl, &mut (), " -l");
+        assert!(repl.is_dirty());
+    }
+
+    #[test]
+    fn exit_refused_when_dirty() {
+        let mut repl = Repl::new();
+        repl.set_exit_policy(ExitPolicy::RefuseWhenDirty);
+        type_str(&mut repl, &mut (), "ls");
+        feed(&mut repl, &mut (), ctrl('d'));
+        assert!(repl.take_bell());
+
+        repl.current_input_mut().clear();
+        assert_eq!(
+            repl.feed_key_event(&mut (), ctrl('d')).unwrap(),
+            ControlFlow::Break(())
+        );
+    }
+
+    #[test]
+    fn exit_confirmed_when_dirty() {
+        let mut repl = Repl::new();
+        repl.set_exit_policy(ExitPolicy::ConfirmWhenDirty);
+        type_str(&mut repl, &mut (), "ls");
+
+        feed(&mut repl, &mut (), ctrl('d'));
+        assert!(repl.exit_pending());
+        type_str(&mut repl, &mut (), "x");
+        assert!(!repl.exit_pending());
+        feed(&mut repl, &mut (), ctrl('q'));
+        assert_eq!(
+            repl.feed_key_event(&mut (), ctrl('q')).unwrap(),
+            ControlFlow::Break(())
+        );
+        assert_eq!(input_string(&repl), "lsx");
+    }
+
+    #[test]
+    fn ctrl_d_policies() {
+        let exits =
+            |repl: &mut Repl<32>| repl.feed_key_event(&mut (), ctrl('d')).unwrap().is_break();
+        for policy in [
+            CtrlDPolicy::Exit,
+            CtrlDPolicy::ExitWhenEmpty,
+            CtrlDPolicy::DeleteOrExit,
+        ] {
+            let mut repl = Repl::new();
+            repl.set_ctrl_d_policy(policy);
+            assert!(exits(&mut repl));
+        }
+
+        let mut repl = Repl::new();
+        type_str(&mut repl, &mut (), "ls");
+        assert!(exits(&mut repl));
+
+        repl.set_ctrl_d_policy(CtrlDPolicy::ExitWhenEmpty);
+        assert!(!exits(&mut repl));
+        assert!(repl.take_bell());
+        assert_eq!(input_string(&repl), "ls");
+
+        repl.set_ctrl_d_policy(CtrlDPolicy::DeleteOrExit);
+        feed(&mut repl, &mut (), key(KeyCode::Home));
+        assert!(!exits(&mut repl));
+        assert_eq!(input_string(&repl), "s");
+        assert!(!exits(&mut repl));
+        assert_eq!(input_string(&repl), "");
+        assert!(exits(&mut repl));
+        assert!(repl.feed_key_event(&mut (), ctrl('q')).unwrap().is_break());
+    }
+
+    #[test]
+    fn cursor_style_emitted_once() {
+        let mut repl = Repl::new();
+        let mut out = Vec::new();
+        repl.set_cursor_style(Some(CursorStyle {
+            shape: CursorShape::Line,
+            blinking: false,
+        }));
+        repl.flush_terminal_effects(&mut out).unwrap();
+        assert_eq!(out, b"\x1b[6 q");
+
+        out.clear();
+        repl.flush_terminal_effects(&mut out).unwrap();
+        assert!(out.is_empty());
+
+        repl.set_cursor_style(Some(CursorStyle {
+            shape: CursorShape::UnderScore,
+            blinking: true,
+        }));
+        repl.flush_terminal_effects(&mut out).unwrap();
+        assert_eq!(out, b"\x1b[3 q");
+
+        out.clear();
+        repl.set_cursor_style(None);
+        repl.flush_terminal_effects(&mut out).unwrap();
+        assert_eq!(out, b"\x1b[0 q");
+    }
+
+    #[test]
+    fn cursor_style_applied_when_running() {
+        let mut term = test_terminal(10, 2);
+        let mut repl = Repl::new();
+        repl.set_cursor_style(Some(CursorStyle {
+            shape: CursorShape::Block,
+            blinking: true,
+        }));
+        repl.flush_terminal_effects(&mut io::sink()).unwrap();
+
+        let mut out = Vec::new();
+        let mut events = ScriptedEvents::new([Some(Event::Key(ctrl('d')))]);
+        repl.run_with_events(&mut term, &mut (), &mut events, &mut out)
+            .unwrap();
+        assert_eq!(out, b"\x1b[1 q");
+    }
+
+    #[test]
+    fn prime_from_reader_submits_lines() {
+        let mut repl = Repl::new();
+        repl.set_prompt("\n>");
+        let mut recorder = Recorder::default();
+        repl.prime_from_reader(&mut recorder, "ls\r\n\necho hi\n".as_bytes())
+            .unwrap();
+
+        assert_eq!(recorder.0, ["ls", "", "echo hi"]);
+        assert_eq!(
+            repl.history().iter().collect::<Vec<_>>(),
+            ["ls", "echo hi"].map(|s| s.chars().collect::<Vec<_>>())
+        );
+        assert_eq!(repl.text(), "ls\n>\n>echo hi");
+    }
+
+    #[test]
+    fn undo_keys() {
+        let mut repl = Repl::new();
+        type_str(&mut repl, &mut (), "abc");
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+        feed(&mut repl, &mut (), key(KeyCode::Backspace));
+        assert_eq!(input_string(&repl), "ac");
+
+        // Ctrl+_ as delivered by crossterm on unix terminals
+        feed(&mut repl, &mut (), ctrl('7'));
+        assert_eq!(input_string(&repl), "abc");
+        assert_eq!(repl.cursor_pos_in(Rect::new(0, 0, 10, 1)), (2, 0));
+        feed(&mut repl, &mut (), ctrl('_'));
+        assert_eq!(input_string(&repl), "ab");
+        feed(&mut repl, &mut (), ctrl('z'));
+        feed(&mut repl, &mut (), ctrl('z'));
+        assert_eq!(input_string(&repl), "");
+        assert!(!repl.bell_pending());
+        feed(&mut repl, &mut (), ctrl('z'));
+        assert!(repl.take_bell());
+
+        submit_str(&mut repl, &mut (), "ls");
+        feed(&mut repl, &mut (), ctrl('_'));
+        assert!(repl.take_bell());
+    }
+
+    #[test]
+    fn no_echo_hides_input() {
+        let mut repl = Repl::with_text("pin: ");
+        repl.set_echo(false);
+        type_str(&mut repl, &mut (), "1234");
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+
+        let area = Rect::new(0, 0, 10, 1);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "pin:      ");
+        assert_eq!(repl.cursor_pos_in(area), (5, 0));
+
+        let mut recorder = Recorder::default();
+        feed(&mut repl, &mut recorder, key(KeyCode::Enter));
+        assert_eq!(recorder.0, ["1234"]);
+        assert!(!repl.text().contains("1234"));
+    }
+
+    #[test]
+    fn no_echo_input_not_recorded() {
+        let path = std::env::temp_dir().join(format!("tui_repl_no_echo_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut repl = Repl::new();
+        repl.set_history_file(&path);
+        let mut recorder = Recorder::default();
+        submit_str(&mut repl, &mut recorder, "login");
+        repl.set_echo(false);
+        submit_str(&mut repl, &mut recorder, "secret");
+        repl.set_echo(true);
+
+        assert_eq!(recorder.0, ["login", "secret"]);
+        assert_eq!(repl.history().iter_strings().collect::<Vec<_>>(), ["login"]);
+        assert_eq!(repl.last_command(), Some(&['l', 'o', 'g', 'i', 'n'][..]));
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(!file.contains("secret"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn matching_bracket_highlighted() {
+        let mut repl = Repl::new();
+        let matched = Style::default().fg(Color::Yellow);
+        let unmatched = Style::default().fg(Color::Red);
+        repl.set_bracket_highlight(Some(BracketHighlight {
+            matched,
+            unmatched: Some(unmatched),
+        }));
+        type_str(&mut repl, &mut (), "(a[b]) ]");
+        let area = Rect::new(0, 0, 10, 1);
+        let render = |repl: &mut Repl<32>| {
+            let mut buf = Buffer::empty(area);
+            repl.render(area, &mut buf);
+            (0..8).map(|x| buf.get(x, 0).fg).collect::<Vec<_>>()
+        };
+        let plain = Color::Reset;
+
+        // The unmatched bracket before the cursor
+        let colors = render(&mut repl);
+        assert_eq!(colors[7], Color::Red);
+        assert!(colors[..7].iter().all(|&c| c == plain));
+
+        repl.editor_mut().set_cursor_char_index(2);
+        let colors = render(&mut repl);
+        assert_eq!(colors[2], Color::Yellow);
+        assert_eq!(colors[4], Color::Yellow);
+        assert_eq!(colors.iter().filter(|&&c| c != plain).count(), 2);
+
+        repl.editor_mut().set_cursor_char_index(6);
+        let colors = render(&mut repl);
+        assert_eq!(colors[0], Color::Yellow);
+        assert_eq!(colors[5], Color::Yellow);
+
+        repl.set_bracket_highlight(Some(BracketHighlight {
+            matched,
+            unmatched: None,
+        }));
+        repl.editor_mut().set_cursor_char_index(8);
+        assert!(render(&mut repl).iter().all(|&c| c == plain));
+    }
+
+    #[test]
+    fn show_whitespace_only_affects_rendering() {
+        let mut repl = Repl::new();
+        repl.set_show_whitespace(true);
+        type_str(&mut repl, &mut (), "a b\t ");
+
+        let area = Rect::new(0, 0, 10, 1);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+
+        let plain = tui::buffer::Cell::default().style();
+        let dim = plain.add_modifier(Modifier::DIM);
+        let symbols = (0..5)
+            .map(|x| buf.get(x, 0).symbol.as_str())
+            .collect::<String>();
+        assert_eq!(symbols, "a b→·");
+        assert_eq!(buf.get(1, 0).style(), plain);
+        assert_eq!(buf.get(3, 0).style(), dim);
+        assert_eq!(buf.get(4, 0).style(), dim);
+
+        let mut recorder = Recorder::default();
+        feed(&mut repl, &mut recorder, key(KeyCode::Enter));
+        assert_eq!(recorder.0, ["a b\t "]);
+    }
+
+    #[test]
+    fn truncate_overflowing_lines() {
+        let mut repl = Repl::new();
+        repl.set_overflow(Overflow::Truncate('…'));
+        repl.print("0123456789\nshort\n>abcdefgh");
+
+        let area = Rect::new(0, 0, 6, 3);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buf, Buffer::with_lines(vec!["01234…", "short ", ">abcde"]));
+
+        repl.set_overflow(Overflow::Clip);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buf, Buffer::with_lines(vec!["012345", "short ", ">abcde"]));
+    }
+
+    #[test]
+    fn cursor_char_index_counts_from_start() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.cursor_char_index(), 0);
+        type_str(&mut repl, &mut (), "abcd");
+        assert_eq!(repl.cursor_char_index(), 4);
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+        assert_eq!(repl.cursor_char_index(), 3);
+        feed(&mut repl, &mut (), key(KeyCode::Home));
+        assert_eq!(repl.cursor_char_index(), 0);
+        feed(&mut repl, &mut (), key(KeyCode::Char('x')));
+        assert_eq!(repl.cursor_char_index(), 1);
+        assert_eq!(input_string(&repl), "xabcd");
+    }
+
+    #[test]
+    fn disabled_history() {
+        let mut repl = Repl::new_with_history(History::<0>::new());
+        let mut recorder = Recorder::default();
+        submit_str(&mut repl, &mut recorder, "secret");
+        feed(&mut repl, &mut recorder, key(KeyCode::Up));
+        assert!(repl.take_bell());
+        assert_eq!(input_string(&repl), "");
+        feed(&mut repl, &mut recorder, key(KeyCode::Down));
+
+        assert_eq!(recorder.0, ["secret"]);
+        assert!(repl.history().is_empty());
+    }
+
+    #[test]
+    fn edits_after_shortened_input_dont_panic() {
+        let mut repl = Repl::new_with_history(History::<4>::with_initial([vec!['a']]));
+        type_str(&mut repl, &mut (), "a long command");
+        repl.set_cursor_pos(10);
+        feed(&mut repl, &mut (), key(KeyCode::Up));
+        type_str(&mut repl, &mut (), "b");
+        assert_eq!(input_string(&repl), "ab");
+
+        repl.set_cursor_pos(2);
+        repl.current_input_mut().clear();
+        type_str(&mut repl, &mut (), "c");
+        repl.current_input_mut().clear();
+        for code in [KeyCode::Delete, KeyCode::Backspace] {
+            feed(&mut repl, &mut (), key(code));
+        }
+        type_str(&mut repl, &mut (), "d");
+        assert_eq!(input_string(&repl), "d");
+        assert_eq!(repl.cursor_char_index(), 1);
+    }
+
+    #[test]
+    fn submit_command_keeps_input() {
+        let mut repl = Repl::new();
+        repl.set_prompt("\n>");
+        let mut recorder = Recorder::default();
+        type_str(&mut repl, &mut recorder, "ec");
+        repl.submit_command(&mut recorder, "ls -l").unwrap();
+        repl.submit_command(&mut recorder, "  ").unwrap();
+
+        assert_eq!(recorder.0, ["ls -l", "  "]);
+        assert_eq!(repl.history().iter_strings().collect::<Vec<_>>(), ["ls -l"]);
+        assert_eq!(repl.text(), "ls -l\n>  ");
+        assert_eq!(input_string(&repl), "ec");
+    }
+
+    #[test]
+    fn paged_executor_writes_to_text() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.set_paging(true);
+        repl.set_page_height(3);
+        let mut executor = |cmd: String, out: &mut String| {
+            match cmd.as_str() {
+                "clear" => out.clear(),
+                _ => {
+                    assert!(out.ends_with(&cmd));
+                    out.push_str("\n1\n2\n3\n4\n");
+                }
+            }
+            Ok(())
+        };
+        submit_str(&mut repl, &mut executor, "seq");
+        assert!(repl.more_pending());
+        assert_eq!(repl.text(), "seq\n1\n");
+        feed(&mut repl, &mut executor, key(KeyCode::Char('q')));
+
+        submit_str(&mut repl, &mut executor, "clear");
+        assert!(!repl.more_pending());
+        assert_eq!(repl.text(), "");
+        assert_eq!(repl.block_count(), 0);
+    }
+
+    #[test]
+    fn paging_holds_output_until_space() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.set_paging(true);
+        let area = Rect::new(0, 0, 10, 3);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+
+        let mut executor = |_: String, out: &mut String| {
+            out.push_str("\n1\n2\n3\n4\n5");
+            Ok(())
+        };
+        submit_str(&mut repl, &mut executor, "seq");
+        assert!(repl.more_pending());
+        assert_eq!(repl.text(), "seq\n1\n");
+
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(
+            (0..10)
+                .map(|x| buf.get(x, 2).symbol.as_str())
+                .collect::<String>(),
+            "-- more --"
+        );
+
+        // Typing doesn't edit the input while output is held
+        feed(&mut repl, &mut executor, key(KeyCode::Char('x')));
+        assert_eq!(repl.text(), "seq\n1\n");
+        feed(&mut repl, &mut executor, key(KeyCode::Char(' ')));
+        assert_eq!(repl.text(), "seq\n1\n2\n3\n");
+        feed(&mut repl, &mut executor, key(KeyCode::Enter));
+        assert_eq!(repl.text(), "seq\n1\n2\n3\n4\n");
+        feed(&mut repl, &mut executor, key(KeyCode::Char(' ')));
+        assert!(!repl.more_pending());
+        assert_eq!(repl.text(), "seq\n1\n2\n3\n4\n5");
+        assert_eq!(input_string(&repl), "");
+
+        submit_str(&mut repl, &mut executor, "seq");
+        feed(&mut repl, &mut executor, key(KeyCode::Char('q')));
+        assert_eq!(repl.text(), "seq\n1\n2\n3\n4\n5>seq\n1\n");
+    }
+
+    #[test]
+    fn take_history_after_submitting() {
+        let mut repl = Repl::new();
+        submit_str(&mut repl, &mut (), "ls");
+        submit_str(&mut repl, &mut (), "pwd");
+        type_str(&mut repl, &mut (), "unsubmitted");
+        assert_eq!(
+            repl.take_history().iter_strings().collect::<Vec<_>>(),
+            ["ls", "pwd"]
+        );
+
+        let mut repl = Repl::new();
+        submit_str(&mut repl, &mut (), "ls");
+        let (history, text) = repl.into_parts();
+        assert_eq!(history.iter_strings().collect::<Vec<_>>(), ["ls"]);
+        assert_eq!(text, "ls");
+    }
+
+    #[test]
+    fn empty_submit_policies() {
+        for (policy, calls, text) in [
+            (EmptySubmitPolicy::Execute, 1, ">\n"),
+            (EmptySubmitPolicy::Ignore, 0, ""),
+            (EmptySubmitPolicy::EmitNewline, 0, ">\n"),
+        ] {
+            let mut repl = Repl::new();
+            repl.set_prompt(">");
+            repl.set_ensure_newline(true);
+            repl.emit_prompt();
+            repl.set_empty_submit_policy(policy);
+            let mut recorder = Recorder::default();
+            feed(&mut repl, &mut recorder, key(KeyCode::Enter));
+
+            assert_eq!(recorder.0.len(), calls, "{:?}", policy);
+            assert_eq!(repl.text(), text, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn read_only_ignores_edits() {
+        let mut repl = Repl::new();
+        repl.print("1\n2\n3\n4\n>");
+        type_str(&mut repl, &mut (), "ab");
+        repl.set_read_only(true);
+        let area = Rect::new(0, 0, 5, 2);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+
+        let mut recorder = Recorder::default();
+        type_str(&mut repl, &mut recorder, "cd");
+        for code in [KeyCode::Backspace, KeyCode::Enter, KeyCode::Up] {
+            feed(&mut repl, &mut recorder, key(code));
+        }
+        feed(&mut repl, &mut recorder, ctrl('c'));
+        repl.feed_paste(&mut recorder, "ls\n").unwrap();
+        assert_eq!(input_string(&repl), "ab");
+        assert!(recorder.0.is_empty());
+
+        feed(&mut repl, &mut recorder, key(KeyCode::Left));
+        assert_eq!(repl.cursor_char_index(), 1);
+
+        feed(&mut repl, &mut recorder, key(KeyCode::PageUp));
+        feed(&mut repl, &mut recorder, key(KeyCode::PageUp));
+        assert_eq!(repl.scroll_offset(), 2);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buf, Buffer::with_lines(vec!["2    ", "3    "]));
+
+        assert_eq!(
+            repl.feed_key_event(&mut recorder, ctrl('d')).unwrap(),
+            ControlFlow::Break(())
+        );
+    }
+
+    #[test]
+    fn scroll_and_edit_returns_to_bottom() {
+        let mut repl = Repl::new();
+        repl.print("1\n2\n3\n>");
+        let area = Rect::new(0, 0, 5, 2);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+
+        repl.scroll_up(10);
+        assert_eq!(repl.scroll_offset(), 2);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buf, Buffer::with_lines(vec!["1    ", "2    "]));
+
+        feed(&mut repl, &mut (), key(KeyCode::PageDown));
+        assert_eq!(repl.scroll_offset(), 1);
+        type_str(&mut repl, &mut (), "x");
+        assert_eq!(repl.scroll_offset(), 0);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buf, Buffer::with_lines(vec!["3    ", ">x   "]));
+    }
+
+    #[test]
+    fn output_lines_yields_whole_text() {
+        let mut repl = Repl::new();
+        repl.set_scrollback_limit(Some(3));
+        repl.print("a\nb\r\nc\nd\n>");
+        assert_eq!(repl.output_lines().collect::<Vec<_>>(), ["c", "d", ">"]);
+
+        repl.set_scrollback_limit(None);
+        repl.print("ls\ne\nf\n>");
+        assert_eq!(
+            repl.output_lines().collect::<Vec<_>>(),
+            ["c", "d", ">ls", "e", "f", ">"]
+        );
+    }
+
+    #[test]
+    fn modified_enter_inserts_newline() {
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        let ctrl_enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::CONTROL);
+        let alt_enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT);
+        type_str(&mut repl, &mut recorder, "a");
+        feed(&mut repl, &mut recorder, ctrl_enter);
+        type_str(&mut repl, &mut recorder, "b");
+        feed(&mut repl, &mut recorder, alt_enter);
+        assert_eq!(input_string(&repl), "a\nb\n");
+        assert!(recorder.0.is_empty());
+
+        repl.set_newline_key(NewlineKey::AltEnter);
+        feed(&mut repl, &mut recorder, ctrl_enter);
+        assert_eq!(input_string(&repl), "a\nb\n");
+        feed(&mut repl, &mut recorder, key(KeyCode::Enter));
+        assert_eq!(recorder.0, ["a\nb\n"]);
+    }
+
+    #[test]
+    fn cursor_after_separate_prompt() {
+        let mut repl = Repl::new();
+        repl.print("out");
+        repl.set_prompt("db> ");
+        repl.set_ensure_newline(true);
+        repl.emit_prompt();
+        assert_eq!(repl.text(), "out\n");
+        type_str(&mut repl, &mut (), "select");
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+
+        let area = Rect::new(0, 0, 20, 3);
+        assert_eq!(repl.cursor_pos_in(area), (4 + 5, 1));
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 1), "db> select          ");
+
+        repl.set_prompt("\n$ ");
+        submit_str(&mut repl, &mut (), "");
+        assert_eq!(repl.text(), "out\ndb> select\n");
+        type_str(&mut repl, &mut (), "ab");
+        assert_eq!(repl.cursor_pos_in(area), (4, 2));
+    }
+
+    #[test]
+    fn input_above_text() {
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.set_input_position(InputPosition::Top);
+        repl.set_show_line_numbers(true);
+        repl.print("a\nb\nc\n");
+        repl.emit_prompt();
+        type_str(&mut repl, &mut (), "xy");
+
+        let area = Rect::new(0, 0, 8, 3);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "  > xy  ");
+        assert_eq!(buffer_row(&buf, 1), "2 b     ");
+        assert_eq!(buffer_row(&buf, 2), "3 c     ");
+        assert_eq!(repl.cursor_pos_in(area), (6, 0));
+
+        repl.set_show_line_numbers(false);
+        feed(
+            &mut repl,
+            &mut (),
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT),
+        );
+        type_str(&mut repl, &mut (), "z");
+        let area = Rect::new(0, 0, 8, 4);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "> xy    ");
+        assert_eq!(buffer_row(&buf, 1), "z       ");
+        assert_eq!(buffer_row(&buf, 2), "b       ");
+        assert_eq!(buffer_row(&buf, 3), "c       ");
+        assert_eq!(repl.cursor_pos_in(area), (1, 1));
+    }
+
+    #[test]
+    fn set_text_resets_scroll() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.print("1\n2\n3\n4\n");
+        repl.emit_prompt();
+        let area = Rect::new(0, 0, 4, 2);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        repl.scroll_up(2);
+        assert_eq!(repl.scroll_offset(), 2);
+
+        repl.set_scrollback_limit(Some(2));
+        repl.set_text("a\nb\nc\n");
+        assert_eq!(repl.text(), "c\n");
+        assert_eq!(repl.scroll_offset(), 0);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "c   ");
+        assert_eq!(buffer_row(&buf, 1), ">   ");
+        assert_eq!(repl.cursor_pos_in(area), (1, 1));
+    }
+
+    #[test]
+    fn at_bottom_until_scrolled_up() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.print("1\n2\n3\n4\n");
+        repl.emit_prompt();
+        repl.set_page_height(2);
+        assert!(repl.is_at_bottom());
+
+        repl.scroll_up(1);
+        assert!(!repl.is_at_bottom());
+        repl.scroll_up(10);
+        assert_eq!(repl.scroll_offset(), 3);
+        repl.scroll_down(2);
+        assert!(!repl.is_at_bottom());
+        repl.scroll_down(1);
+        assert!(repl.is_at_bottom());
+        repl.scroll_down(1);
+        assert!(repl.is_at_bottom());
+
+        repl.scroll_up(2);
+        type_str(&mut repl, &mut (), "a");
+        assert!(repl.is_at_bottom());
+    }
+
+    #[test]
+    fn separator_between_text_and_input() {
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.set_separator(Some(Separator {
+            symbol: '─',
+            style: Style::default(),
+        }));
+        repl.print("a\nb\nc\n");
+        repl.emit_prompt();
+        type_str(&mut repl, &mut (), "xy");
+
+        let area = Rect::new(0, 0, 5, 4);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "b    ");
+        assert_eq!(buffer_row(&buf, 1), "c    ");
+        assert_eq!(buffer_row(&buf, 2), "─────");
+        assert_eq!(buffer_row(&buf, 3), "> xy ");
+        assert_eq!(repl.cursor_pos_in(area), (4, 3));
+
+        let area = Rect::new(0, 0, 5, 6);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 3), "─────");
+        assert_eq!(buffer_row(&buf, 4), "> xy ");
+        assert_eq!(repl.cursor_pos_in(area), (4, 4));
+
+        repl.set_input_position(InputPosition::Top);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "> xy ");
+        assert_eq!(buffer_row(&buf, 1), "─────");
+        assert_eq!(buffer_row(&buf, 2), "a    ");
+        assert_eq!(repl.cursor_pos_in(area), (4, 0));
+    }
+
+    #[test]
+    fn validator_shows_error_and_blocks_submit() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.emit_prompt();
+        repl.set_validator(|input: &[char]| {
+            if input.iter().filter(|&&c| c == '"').count() % 2 == 1 {
+                Err(String::from("unterminated string"))
+            } else {
+                Ok(())
+            }
+        });
+        let mut recorder = Recorder::default();
+        type_str(&mut repl, &mut recorder, "a \"b");
+        assert_eq!(repl.validation_error(), Some("unterminated string"));
+
+        let area = Rect::new(0, 0, 30, 1);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), ">a \"b  unterminated string    ");
+        assert_eq!(repl.cursor_pos_in(area), (5, 0));
+
+        repl.set_block_invalid_submit(true);
+        feed(&mut repl, &mut recorder, key(KeyCode::Enter));
+        assert!(recorder.0.is_empty());
+        assert!(repl.take_bell());
+
+        type_str(&mut repl, &mut recorder, "\"");
+        assert_eq!(repl.validation_error(), None);
+        feed(&mut repl, &mut recorder, key(KeyCode::Enter));
+        assert_eq!(recorder.0, ["a \"b\""]);
+
+        repl.set_block_invalid_submit(false);
+        submit_str(&mut repl, &mut recorder, "\"");
+        assert_eq!(recorder.0, ["a \"b\"", "\""]);
+        assert_eq!(repl.validation_error(), None);
+    }
+
+    #[test]
+    fn validated_on_every_input_change() {
+        let mut repl = Repl::new();
+        repl.set_validator(|input: &[char]| match input.contains(&'!') {
+            true => Err(String::from("no !")),
+            false => Ok(()),
+        });
+        let mut recorder = Recorder::default();
+        repl.feed_paste(&mut recorder, "a!").unwrap();
+        assert_eq!(repl.validation_error(), Some("no !"));
+        repl.set_input("ok");
+        assert_eq!(repl.validation_error(), None);
+        repl.insert_str_at_cursor("!");
+        assert_eq!(repl.validation_error(), Some("no !"));
+        repl.submit(&mut recorder).unwrap();
+        assert_eq!(repl.validation_error(), None);
+
+        let mut snapshot = repl.snapshot();
+        snapshot.input = String::from("!");
+        repl.restore(snapshot);
+        assert_eq!(repl.validation_error(), Some("no !"));
+
+        repl.current_input_mut().clear();
+        assert_eq!(repl.validation_error(), None);
+        repl.current_input_mut().push('!');
+        feed(&mut repl, &mut recorder, key(KeyCode::Left));
+        assert_eq!(repl.validation_error(), Some("no !"));
+    }
+
+    #[test]
+    fn construct_with_text() {
+        let repl = Repl::with_text("welcome\n>");
+        assert_eq!(repl.text(), "welcome\n>");
+        assert!(repl.history().is_empty());
+
+        let history = History::<4>::with_initial([vec!['l', 's']]);
+        let repl = Repl::with_text_and_history(">", history);
+        assert_eq!(repl.text(), ">");
+        assert_eq!(repl.history().newest(), Some(['l', 's'].as_slice()));
+    }
+
+    #[test]
+    fn zero_sized_areas() {
+        let mut repl = Repl::new();
+        repl.set_prompt("> ");
+        repl.print("out\nmore\n");
+        repl.emit_prompt();
+        type_str(&mut repl, &mut (), "abc");
+        repl.set_show_line_numbers(true);
+
+        for position in [InputPosition::Bottom, InputPosition::Top] {
+            repl.set_input_position(position);
+            for (width, height) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let area = Rect::new(0, 0, width, height);
+                let mut buf = Buffer::empty(area);
+                (&mut repl).render(area, &mut buf);
+                let mut state = ReplState::default();
+                StatefulWidget::render(&repl, area, &mut buf, &mut state);
+                assert_eq!(repl.cursor_pos_in(area), (0, 0));
+            }
+        }
+        assert_eq!(util::last_lines("a\nb", 0), "");
+    }
+
+    #[test]
+    fn two_line_prompt() {
+        let mut repl = Repl::new();
+        repl.set_prompt("== db ==\n> ");
+        repl.print("out\n");
+        repl.emit_prompt();
+        type_str(&mut repl, &mut (), "abc");
+        feed(&mut repl, &mut (), key(KeyCode::Left));
+
+        let area = Rect::new(0, 0, 10, 4);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "out       ");
+        assert_eq!(buffer_row(&buf, 1), "== db ==  ");
+        assert_eq!(buffer_row(&buf, 2), "> abc     ");
+        assert_eq!(repl.cursor_pos_in(area), (4, 2));
+
+        let area = Rect::new(0, 0, 10, 2);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "== db ==  ");
+        assert_eq!(buffer_row(&buf, 1), "> abc     ");
+        assert_eq!(repl.cursor_pos_in(area), (4, 1));
+
+        feed(&mut repl, &mut (), key(KeyCode::Enter));
+        assert_eq!(repl.text(), "out\n== db ==\n> abc");
+    }
+
+    /// Writer which can be inspected after being moved into the repl
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn output_sink_receives_output() {
+        let mut repl = Repl::new();
+        let sink = SharedBuf::default();
+        repl.set_output_sink(sink.clone());
+        repl.println("hello");
+        let mut executor = |cmd: String, out: &mut String| {
+            out.push_str(&format!("\nran {}\n", cmd));
+            Ok(())
+        };
+        submit_str(&mut repl, &mut executor, "ls");
+
+        assert_eq!(repl.text(), "hello\nls\nran ls\n");
+        assert_eq!(&*sink.0.lock().unwrap(), b"hello\n\nran ls\n");
+
+        repl.remove_output_sink();
+        repl.print("dropped");
+        assert_eq!(sink.0.lock().unwrap().len(), 14);
+    }
+
+    #[test]
+    fn output_sink_receives_held_output_once() {
+        let mut repl = Repl::new();
+        let sink = SharedBuf::default();
+        repl.set_output_sink(sink.clone());
+        repl.set_paging(true);
+        repl.set_page_height(3);
+        let mut executor = |cmd: String, out: &mut String| {
+            out.push_str(&format!("{0}1\n{0}2\n{0}3\n", cmd));
+            Ok(())
+        };
+        submit_str(&mut repl, &mut executor, "a");
+        assert!(repl.more_pending());
+        repl.submit_command(&mut executor, "b").unwrap();
+
+        assert_eq!(&*sink.0.lock().unwrap(), b"a1\na2\na3\nb1\nb2\nb3\n");
+    }
+
+    #[test]
+    fn ctrl_enter_as_submit_key() {
+        let mut repl = Repl::new();
+        repl.set_submit_key(SubmitKey::CtrlEnter);
+        let mut recorder = Recorder::default();
+        type_str(&mut repl, &mut recorder, "a");
+        feed(&mut repl, &mut recorder, key(KeyCode::Enter));
+        type_str(&mut repl, &mut recorder, "b");
+        feed(
+            &mut repl,
+            &mut recorder,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT),
+        );
+        assert!(recorder.0.is_empty());
+        assert_eq!(input_string(&repl), "a\nb\n");
+
+        feed(
+            &mut repl,
+            &mut recorder,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::CONTROL),
+        );
+        assert_eq!(recorder.0, ["a\nb\n"]);
+        assert_eq!(input_string(&repl), "");
+    }
+
+    #[test]
+    fn line_number_gutter() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.set_show_line_numbers(true);
+        repl.print("a\nb\n");
+        repl.emit_prompt();
+        type_str(&mut repl, &mut (), "x");
+        let area = Rect::new(0, 0, 8, 3);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "1 a     ");
+        assert_eq!(buffer_row(&buf, 1), "2 b     ");
+        assert_eq!(buffer_row(&buf, 2), "  >x    ");
+        assert_eq!(repl.cursor_pos_in(area), (4, 2));
+
+        for n in 3..=12 {
+            repl.print(&format!("l{}\n", n));
+        }
+        let area = Rect::new(0, 0, 8, 4);
+        let mut buf = Buffer::empty(area);
+        (&mut repl).render(area, &mut buf);
+        assert_eq!(buffer_row(&buf, 0), "10 l10  ");
+        assert_eq!(buffer_row(&buf, 2), "12 l12  ");
+        assert_eq!(buffer_row(&buf, 3), "   >x   ");
+        assert_eq!(repl.cursor_pos_in(area), (5, 3));
+    }
+
+    #[test]
+    fn render_as_stateful_widget() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.print("out\n");
+        repl.emit_prompt();
+        type_str(&mut repl, &mut (), "ls");
+        let mut term = test_terminal(10, 3);
+        let mut state = ReplState::default();
+        term.draw(|f| f.render_stateful_widget(&repl, f.size(), &mut state))
+            .unwrap();
+        assert_eq!(row(&term, 0), "out       ");
+        assert_eq!(row(&term, 1), ">ls       ");
+        assert!(state.text_cache.is_some());
+
+        repl.print("more\n");
+        term.draw(|f| f.render_stateful_widget(&repl, f.size(), &mut state))
+            .unwrap();
+        assert_eq!(row(&term, 1), "more      ");
+        assert_eq!(row(&term, 2), ">ls       ");
+        assert_eq!(repl.text(), "out\nmore\n");
+    }
+
+    #[test]
+    fn typing_reuses_text_lines() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        repl.print("out\n");
+        repl.emit_prompt();
+        let mut term = test_terminal(10, 2);
+        assert!(repl.needs_full_redraw());
+        repl.draw(&mut term).unwrap();
+        assert!(!repl.needs_full_redraw());
+
+        type_str(&mut repl, &mut (), "ls");
+        assert!(!repl.needs_full_redraw());
+        repl.draw(&mut term).unwrap();
+        assert_eq!(row(&term, 1), ">ls       ");
+
+        repl.text_mut().insert(0, 'x');
+        assert!(repl.needs_full_redraw());
+        repl.draw(&mut term).unwrap();
+        assert_eq!(row(&term, 0), "xout      ");
+
+        submit_str(&mut repl, &mut (), "");
+        assert!(repl.needs_full_redraw());
+    }
+
+    /// Backend whose draw always fails
+    struct FailingBackend(TestBackend);
+
+    impl Backend for FailingBackend {
+        fn draw<'a, I>(&mut self, _content: I) -> io::Result<()>
+        where
+            I: Iterator<Item = (u16, u16, &'a tui::buffer::Cell)>,
+        {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "draw failed"))
+        }
+
+        fn hide_cursor(&mut self) -> io::Result<()> {
+            self.0.hide_cursor()
+        }
+
+        fn show_cursor(&mut self) -> io::Result<()> {
+            self.0.show_cursor()
+        }
+
+        fn get_cursor(&mut self) -> io::Result<(u16, u16)> {
+            self.0.get_cursor()
+        }
+
+        fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()> {
+            self.0.set_cursor(x, y)
+        }
+
+        fn clear(&mut self) -> io::Result<()> {
+            self.0.clear()
+        }
+
+        fn size(&self) -> io::Result<Rect> {
+            self.0.size()
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    #[test]
+    fn errors_tell_failing_stage() {
+        let mut repl = Repl::new();
+        let mut term = Terminal::new(FailingBackend(TestBackend::new(5, 1))).unwrap();
+        let mut events = ScriptedEvents::new([]);
+        let err = repl
+            .run_with_events(&mut term, &mut (), &mut events, &mut io::sink())
+            .unwrap_err();
+        assert!(matches!(err, ReplError::Draw(_)), "{:?}", err);
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::BrokenPipe);
+
+        let mut term = test_terminal(5, 1);
+        let err = repl
+            .run_with_events(&mut term, &mut (), &mut events, &mut io::sink())
+            .unwrap_err();
+        assert!(matches!(err, ReplError::Events(_)), "{:?}", err);
+
+        let mut executor = |_: String, _: &mut String| Err(io::Error::other("failed"));
+        let mut events = ScriptedEvents::new([Some(Event::Key(key(KeyCode::Enter)))]);
+        let err = repl
+            .run_with_events(&mut term, &mut executor, &mut events, &mut io::sink())
+            .unwrap_err();
+        assert!(matches!(err, ReplError::Executor(_)), "{:?}", err);
+        assert_eq!(err.to_string(), "failed to execute a command");
+        assert_eq!(
+            std::error::Error::source(&err).map(ToString::to_string),
+            Some(String::from("failed"))
+        );
+    }
+
+    #[test]
+    fn streamed_output_shows_progressively() {
+        let mut repl = Repl::new();
+        repl.set_prompt(">");
+        let out = repl.output_handle();
+        let mut executor = {
+            let out = out.clone();
+            move |_: String, _: &mut String| {
+                out.println("");
+                out.println("line 1");
+                Ok(())
+            }
+        };
+        let mut term = test_terminal(8, 3);
+        let mut events = ScriptedEvents::new([Some(Event::Key(key(KeyCode::Enter))), None, None]);
+        let rows = |term: &Terminal<TestBackend>| (0..3).map(|y| row(term, y)).collect::<Vec<_>>();
+
+        assert!(repl
+            .step(&mut term, &mut executor, &mut events, Duration::ZERO)
+            .unwrap()
+            .is_continue());
+        step(&mut repl, &mut term, &mut events);
+        assert_eq!(rows(&term), ["        ", "line 1  ", ">       "]);
+
+        out.println("line 2");
+        step(&mut repl, &mut term, &mut events);
+        assert_eq!(rows(&term), ["line 1  ", "line 2  ", ">       "]);
+        assert_eq!(repl.text(), "\nline 1\nline 2\n");
+
+        assert!(!repl.receive_output());
+        out.print("line 3");
+        assert!(repl.receive_output());
+        drop(repl);
+        assert!(!out.print("dropped"));
+    }
 
-        let prev_len = self.text.len();
-        self.text.extend(self.current_input.iter());
+    #[test]
+    fn ctrl_g_clears_input() {
+        let mut repl = Repl::new();
+        let mut recorder = Recorder::default();
+        type_str(&mut repl, &mut recorder, "some command");
+        feed(&mut repl, &mut recorder, key(KeyCode::Left));
+        feed(&mut repl, &mut recorder, ctrl('g'));
 
-        let p = Paragraph::new(util::get_visible_text(&self.text, max_height as usize));
-        p.render(area, buf);
-        self.text.truncate(prev_len);
+        assert_eq!(input_string(&repl), "");
+        assert_eq!(repl.cursor_char_index(), 0);
+        assert!(recorder.0.is_empty());
+        assert!(repl.history().is_empty());
     }
 }
@@ -0,0 +1,94 @@
+//! "Did you mean" suggestions for unknown commands
+
+use std::mem;
+
+/// The default minimum similarity a command needs to be suggested
+pub const DEFAULT_THRESHOLD: f64 = 0.5;
+
+/// Compute the edit distance between two strings, counting swaps of adjacent chars as one edit
+pub fn distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // Rows of the distance matrix for the prefixes of a, two rows back are needed for swaps
+    let mut prev2 = vec![0; b.len() + 1];
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut cur = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        mem::swap(&mut prev2, &mut prev);
+        mem::swap(&mut prev, &mut cur);
+    }
+
+    prev[b.len()]
+}
+
+/// The similarity of two strings between 0 (nothing in common) and 1 (equal)
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let max_len = a.chars().count().max(b.chars().count());
+    if max_len == 0 {
+        1.0
+    } else {
+        1.0 - distance(a, b) as f64 / max_len as f64
+    }
+}
+
+/// Get the command most similar to the input if it reaches [DEFAULT_THRESHOLD]
+pub fn suggest<'a>(input: &str, commands: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    suggest_with_threshold(input, commands, DEFAULT_THRESHOLD)
+}
+
+/// Get the command most similar to the input if its similarity is at least `threshold`
+///
+/// On equal similarity the command coming first is suggested.
+pub fn suggest_with_threshold<'a>(
+    input: &str,
+    commands: impl IntoIterator<Item = &'a str>,
+    threshold: f64,
+) -> Option<&'a str> {
+    let mut best = None;
+    for command in commands {
+        let sim = similarity(input, command);
+        if sim >= threshold && !best.is_some_and(|(_, best_sim)| sim <= best_sim) {
+            best = Some((command, sim));
+        }
+    }
+
+    best.map(|(command, _)| command)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const COMMANDS: [&str; 5] = ["add", "remove", "list", "clear", "exit"];
+
+    #[test]
+    fn distances() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("abc", ""), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("gti", "git"), 1);
+    }
+
+    #[test]
+    fn suggests_closest() {
+        assert_eq!(suggest("remvoe", COMMANDS), Some("remove"));
+        assert_eq!(suggest("lsit", COMMANDS), Some("list"));
+        assert_eq!(suggest("clr", COMMANDS), Some("clear"));
+    }
+
+    #[test]
+    fn nothing_close() {
+        assert_eq!(suggest("frobnicate", COMMANDS), None);
+        assert_eq!(suggest("xyz", COMMANDS), None);
+        assert_eq!(suggest_with_threshold("lst", COMMANDS, 0.9), None);
+    }
+}
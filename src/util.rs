@@ -1,5 +1,145 @@
+use std::borrow::Cow;
+
+use tui::text::{Span, Spans};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Get the text after the `max_height`th line break from its end, the last `max_height + 1` lines
 pub fn get_visible_text(text: &str, max_height: usize) -> String {
-    let mut line_breaks = text.char_indices().rev().filter_map(|(ix,c)| (c=='\n').then(|| ix));
-    let first_line = line_breaks.nth(max_height as usize).map(|n| n + 1).unwrap_or(0);
-    String::from(&text[first_line..])
-}
\ No newline at end of file
+    String::from(last_lines(text, max_height.saturating_add(1)))
+}
+
+/// Get the last `lines` lines of the text, nothing if `lines` is 0
+pub(crate) fn last_lines(text: &str, lines: usize) -> &str {
+    let Some(nth) = lines.checked_sub(1) else {
+        return "";
+    };
+    let mut line_breaks = text
+        .char_indices()
+        .rev()
+        .filter(|&(_, c)| c == '\n')
+        .map(|(ix, _)| ix);
+    let first_line = line_breaks.nth(nth).map(|n| n + 1).unwrap_or(0);
+    &text[first_line..]
+}
+
+/// Cut the line to `width` columns, ending it with `marker` if it is cut
+///
+/// Wide chars take two columns, a wide char which doesn't fit completely is cut as well.
+pub fn truncate_line(line: &str, width: usize, marker: char) -> Cow<'_, str> {
+    if line.width() <= width {
+        return Cow::Borrowed(line);
+    }
+    let Some(room) = width.checked_sub(marker.width().unwrap_or(0)) else {
+        return Cow::Borrowed("");
+    };
+
+    let mut used = 0;
+    let end = line
+        .char_indices()
+        .find_map(|(idx, c)| {
+            used += c.width().unwrap_or(0);
+            (used > room).then_some(idx)
+        })
+        .unwrap_or(line.len());
+    Cow::Owned(format!("{}{}", &line[..end], marker))
+}
+
+/// Replace "\r\n" and lone '\r' with '\n'
+pub fn normalize_newlines(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Append a span to the lines, starting a new line at every line break in its content
+pub(crate) fn push_multiline_span<'a>(lines: &mut Vec<Spans<'a>>, span: Span<'a>) {
+    for (i, part) in span.content.split('\n').enumerate() {
+        if i != 0 || lines.is_empty() {
+            lines.push(Spans::default());
+        }
+        if !part.is_empty() {
+            if let Some(line) = lines.last_mut() {
+                line.0.push(Span::styled(part.to_owned(), span.style));
+            }
+        }
+    }
+}
+
+/// Remove the oldest lines of the text so that at most `max_lines` lines remain
+///
+/// The text is only ever cut directly after a line break, so at least one line is kept and
+/// multibyte chars are never split.
+pub fn trim_lines(text: &mut String, max_lines: usize) {
+    let cut = text
+        .char_indices()
+        .rev()
+        .filter(|&(_, c)| c == '\n')
+        .nth(max_lines.saturating_sub(1))
+        .map(|(ix, _)| ix + 1);
+    if let Some(cut) = cut {
+        text.drain(..cut);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn visible_text_includes_line_after_height() {
+        assert_eq!(get_visible_text("a\nb\nc", 1), "b\nc");
+        assert_eq!(get_visible_text("a\nb\n", 1), "b\n");
+        assert_eq!(get_visible_text("a\nb", 5), "a\nb");
+        assert_eq!(get_visible_text("a\nb", 0), "b");
+    }
+
+    #[test]
+    fn last_lines_counted_exactly() {
+        assert_eq!(last_lines("a\nb\nc", 2), "b\nc");
+        assert_eq!(last_lines("a\nb\n", 2), "b\n");
+        assert_eq!(last_lines("a\nb", 5), "a\nb");
+        assert_eq!(last_lines("a\nb", 0), "");
+    }
+
+    #[test]
+    fn trim_keeps_newest_lines() {
+        let mut text = String::from("a\nb\nc\n>");
+        trim_lines(&mut text, 2);
+        assert_eq!(text, "c\n>");
+        trim_lines(&mut text, 2);
+        assert_eq!(text, "c\n>");
+        trim_lines(&mut text, 0);
+        assert_eq!(text, ">");
+    }
+
+    #[test]
+    fn normalizes_newlines() {
+        assert_eq!(normalize_newlines("a\r\nb\rc\n"), "a\nb\nc\n");
+        assert_eq!(normalize_newlines("\r\r\n"), "\n\n");
+        assert!(matches!(normalize_newlines("a\nb"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn trim_multibyte_lines() {
+        let mut text = String::from("äö\n€\n🦀🦀\n");
+        trim_lines(&mut text, 2);
+        assert_eq!(text, "🦀🦀\n");
+        trim_lines(&mut text, 1);
+        assert_eq!(text, "");
+        assert_eq!(last_lines("ä\n€\n🦀", 2), "€\n🦀");
+        assert_eq!(truncate_line("äöü€", 3, '…'), "äö…");
+    }
+
+    #[test]
+    fn truncate_marks_cut_lines() {
+        assert_eq!(truncate_line("abcdef", 4, '…'), "abc…");
+        assert_eq!(truncate_line("abcd", 4, '…'), "abcd");
+        assert_eq!(truncate_line("äöüß", 2, '>'), "ä>");
+        assert_eq!(truncate_line("abc", 0, '…'), "");
+        assert_eq!(truncate_line("日本語", 4, '…'), "日…");
+        assert_eq!(truncate_line("a日本", 4, '…'), "a日…");
+        assert_eq!(truncate_line("日本", 4, '…'), "日本");
+    }
+}
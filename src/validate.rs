@@ -0,0 +1,13 @@
+//! Validation of the input while it is typed
+
+/// A validator checking the current input every time it changes
+pub trait Validator {
+    /// Check the input, the error message is shown dimmed after the input
+    fn validate(&self, input: &[char]) -> Result<(), String>;
+}
+
+impl<F: Fn(&[char]) -> Result<(), String>> Validator for F {
+    fn validate(&self, input: &[char]) -> Result<(), String> {
+        self(input)
+    }
+}
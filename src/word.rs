@@ -0,0 +1,103 @@
+//! Word boundaries used by the word wise movement and deletion of the input
+
+/// What separates words
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WordClass {
+    /// Words are separated by whitespace only, like WORDs in vim
+    #[default]
+    Whitespace,
+    /// Words are separated by whitespace and punctuation, punctuation forms words itself
+    Punctuation,
+}
+
+#[derive(PartialEq, Eq)]
+enum CharKind {
+    Space,
+    Punct,
+    Word,
+}
+
+impl WordClass {
+    fn kind(self, c: char) -> CharKind {
+        if c.is_whitespace() {
+            CharKind::Space
+        } else if self == WordClass::Punctuation && c.is_ascii_punctuation() {
+            CharKind::Punct
+        } else {
+            CharKind::Word
+        }
+    }
+}
+
+/// Get the index of the start of the word before `idx`, skipping whitespace
+pub fn prev_boundary(input: &[char], idx: usize, class: WordClass) -> usize {
+    let mut idx = idx.min(input.len());
+    while idx > 0 && class.kind(input[idx - 1]) == CharKind::Space {
+        idx -= 1;
+    }
+    if let Some(&c) = idx.checked_sub(1).and_then(|i| input.get(i)) {
+        let kind = class.kind(c);
+        while idx > 0 && class.kind(input[idx - 1]) == kind {
+            idx -= 1;
+        }
+    }
+
+    idx
+}
+
+/// Get the index of the end of the word after `idx`, skipping whitespace
+pub fn next_boundary(input: &[char], idx: usize, class: WordClass) -> usize {
+    let mut idx = idx.min(input.len());
+    while idx < input.len() && class.kind(input[idx]) == CharKind::Space {
+        idx += 1;
+    }
+    if let Some(&c) = input.get(idx) {
+        let kind = class.kind(c);
+        while idx < input.len() && class.kind(input[idx]) == kind {
+            idx += 1;
+        }
+    }
+
+    idx
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn walk(
+        f: fn(&[char], usize, WordClass) -> usize,
+        start: usize,
+        class: WordClass,
+    ) -> Vec<usize> {
+        let input = "foo.bar baz".chars().collect::<Vec<_>>();
+        let mut positions = Vec::new();
+        let mut idx = start;
+        loop {
+            let next = f(&input, idx, class);
+            if next == idx {
+                return positions;
+            }
+            positions.push(next);
+            idx = next;
+        }
+    }
+
+    #[test]
+    fn whitespace_boundaries() {
+        assert_eq!(walk(prev_boundary, 11, WordClass::Whitespace), [8, 0]);
+        assert_eq!(walk(next_boundary, 0, WordClass::Whitespace), [7, 11]);
+    }
+
+    #[test]
+    fn punctuation_boundaries() {
+        assert_eq!(
+            walk(prev_boundary, 11, WordClass::Punctuation),
+            [8, 4, 3, 0]
+        );
+        assert_eq!(
+            walk(next_boundary, 0, WordClass::Punctuation),
+            [3, 4, 7, 11]
+        );
+    }
+}